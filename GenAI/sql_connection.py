@@ -34,7 +34,19 @@ async def fetch_sales_data(db_config: DatabaseConfig):
         return []
 
     try:
-        rows = await conn.fetch("SELECT * FROM sales ORDER BY sale_id DESC;")
+        # One row per sale, with its lines as parallel arrays in entry order
+        rows = await conn.fetch("""
+            SELECT s.sale_id,
+                   array_agg(l.product_name ORDER BY l.line_id),
+                   array_agg(l.quantity ORDER BY l.line_id),
+                   array_agg(l.unit_price ORDER BY l.line_id),
+                   s.total_price, s.sold_by, s.sale_date,
+                   array_agg(l.category ORDER BY l.line_id)
+            FROM sales s
+            JOIN sale_lines l ON l.sale_id = s.sale_id
+            GROUP BY s.sale_id
+            ORDER BY s.sale_id DESC;
+        """)
         return rows
     finally:
         await conn.close()
@@ -46,7 +58,19 @@ async def fetch_orders_data(db_config: DatabaseConfig):
         return []
 
     try:
-        rows = await conn.fetch("SELECT * FROM orders ORDER BY order_id DESC;")
+        # One row per order, with its lines as parallel arrays in entry order
+        rows = await conn.fetch("""
+            SELECT o.order_id, o.supplier_name,
+                   array_agg(l.product_name ORDER BY l.line_id),
+                   array_agg(l.quantity ORDER BY l.line_id),
+                   array_agg(l.category ORDER BY l.line_id),
+                   array_agg(l.unit_cost ORDER BY l.line_id),
+                   o.order_date, o.status
+            FROM orders o
+            JOIN order_lines l ON l.order_id = o.order_id
+            GROUP BY o.order_id
+            ORDER BY o.order_id DESC;
+        """)
         return rows
     finally:
         await conn.close()
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE api_keys (
+    key_id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL,
+    key_hash VARCHAR(64) NOT NULL UNIQUE,
+    permissions TEXT NOT NULL,
+    created_by INT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX logs_entity_idx;
+DROP INDEX logs_timestamp_idx;
+
+ALTER TABLE logs
+    DROP COLUMN actor_kind,
+    DROP COLUMN entity,
+    DROP COLUMN entity_id,
+    DROP COLUMN before_state,
+    DROP COLUMN after_state,
+    DROP COLUMN ip;
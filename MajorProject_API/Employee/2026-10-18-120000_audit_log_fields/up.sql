@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE logs
+    ADD COLUMN actor_kind TEXT NOT NULL DEFAULT 'system',
+    ADD COLUMN entity TEXT,
+    ADD COLUMN entity_id TEXT,
+    ADD COLUMN before_state JSONB,
+    ADD COLUMN after_state JSONB,
+    ADD COLUMN ip TEXT;
+
+CREATE INDEX logs_timestamp_idx ON logs (timestamp);
+CREATE INDEX logs_entity_idx ON logs (entity, entity_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE logs
+    DROP COLUMN prev_hash,
+    DROP COLUMN row_hash;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Rows written before this migration keep NULL hashes and sit in front of the chain
+ALTER TABLE logs
+    ADD COLUMN prev_hash TEXT,
+    ADD COLUMN row_hash TEXT;
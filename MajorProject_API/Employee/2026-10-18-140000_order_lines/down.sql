@@ -0,0 +1,30 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders
+    ADD COLUMN product_id TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN quantity_ordered INT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN categories TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN price FLOAT4[] NOT NULL DEFAULT '{}';
+
+UPDATE orders
+SET product_id = lines.product_id,
+    quantity_ordered = lines.quantity_ordered,
+    categories = lines.categories,
+    price = lines.price
+FROM (
+    SELECT order_id,
+           array_agg(product_name ORDER BY line_id) AS product_id,
+           array_agg(quantity ORDER BY line_id) AS quantity_ordered,
+           array_agg(category ORDER BY line_id) AS categories,
+           array_agg(unit_cost::FLOAT4 ORDER BY line_id) AS price
+    FROM order_lines
+    GROUP BY order_id
+) AS lines
+WHERE orders.order_id = lines.order_id;
+
+ALTER TABLE orders
+    ALTER COLUMN product_id DROP DEFAULT,
+    ALTER COLUMN quantity_ordered DROP DEFAULT,
+    ALTER COLUMN categories DROP DEFAULT,
+    ALTER COLUMN price DROP DEFAULT;
+
+DROP TABLE order_lines;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE order_lines (
+    line_id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL REFERENCES orders (order_id) ON DELETE CASCADE,
+    sku TEXT,
+    product_name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    quantity INT NOT NULL,
+    unit_cost FLOAT8 NOT NULL
+);
+CREATE INDEX order_lines_order_id ON order_lines (order_id);
+
+-- One line per array position. Arrays of unequal length are padded with NULL by unnest.
+INSERT INTO order_lines (order_id, product_name, category, quantity, unit_cost)
+SELECT orders.order_id, line.product_name, COALESCE(line.category, ''), COALESCE(line.quantity, 0), COALESCE(line.unit_cost, 0)
+FROM orders,
+     unnest(orders.product_id, orders.categories, orders.quantity_ordered, orders.price)
+         WITH ORDINALITY AS line (product_name, category, quantity, unit_cost, position)
+WHERE line.product_name IS NOT NULL
+ORDER BY orders.order_id, line.position;
+
+ALTER TABLE orders
+    DROP COLUMN product_id,
+    DROP COLUMN quantity_ordered,
+    DROP COLUMN categories,
+    DROP COLUMN price;
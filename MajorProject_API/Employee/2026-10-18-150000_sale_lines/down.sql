@@ -0,0 +1,31 @@
+-- This file should undo anything in `up.sql`
+-- Totals keep their corrected values; discounts are lost.
+ALTER TABLE sales
+    ADD COLUMN product_id TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN quantity_sold INT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN price FLOAT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN categories TEXT[] NOT NULL DEFAULT '{}';
+
+UPDATE sales
+SET product_id = lines.product_id,
+    quantity_sold = lines.quantity_sold,
+    price = lines.price,
+    categories = lines.categories
+FROM (
+    SELECT sale_id,
+           array_agg(product_name ORDER BY line_id) AS product_id,
+           array_agg(quantity ORDER BY line_id) AS quantity_sold,
+           array_agg(unit_price ORDER BY line_id) AS price,
+           array_agg(category ORDER BY line_id) AS categories
+    FROM sale_lines
+    GROUP BY sale_id
+) AS lines
+WHERE sales.sale_id = lines.sale_id;
+
+ALTER TABLE sales
+    ALTER COLUMN product_id DROP DEFAULT,
+    ALTER COLUMN quantity_sold DROP DEFAULT,
+    ALTER COLUMN price DROP DEFAULT,
+    ALTER COLUMN categories DROP DEFAULT;
+
+DROP TABLE sale_lines;
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+CREATE TABLE sale_lines (
+    line_id SERIAL PRIMARY KEY,
+    sale_id INT NOT NULL REFERENCES sales (sale_id) ON DELETE CASCADE,
+    product_name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    quantity INT NOT NULL,
+    unit_price FLOAT8 NOT NULL,
+    discount FLOAT8 NOT NULL DEFAULT 0,
+    line_total FLOAT8 NOT NULL
+);
+CREATE INDEX sale_lines_sale_id ON sale_lines (sale_id);
+
+-- One line per array position. Arrays of unequal length are padded with NULL by unnest.
+INSERT INTO sale_lines (sale_id, product_name, category, quantity, unit_price, line_total)
+SELECT sales.sale_id, line.product_name, COALESCE(line.category, ''), COALESCE(line.quantity, 0), COALESCE(line.unit_price, 0),
+       ROUND((COALESCE(line.quantity, 0) * COALESCE(line.unit_price, 0))::NUMERIC, 2)::FLOAT8
+FROM sales,
+     unnest(sales.product_id, sales.categories, sales.quantity_sold, sales.price)
+         WITH ORDINALITY AS line (product_name, category, quantity, unit_price, position)
+WHERE line.product_name IS NOT NULL
+ORDER BY sales.sale_id, line.position;
+
+-- The stored totals summed unit prices without their quantities
+UPDATE sales
+SET total_price = COALESCE((SELECT SUM(line_total) FROM sale_lines WHERE sale_lines.sale_id = sales.sale_id), 0);
+
+ALTER TABLE sales
+    DROP COLUMN product_id,
+    DROP COLUMN quantity_sold,
+    DROP COLUMN price,
+    DROP COLUMN categories;
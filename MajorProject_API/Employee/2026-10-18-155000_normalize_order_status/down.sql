@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+-- The original spellings are not kept, so there is nothing to restore
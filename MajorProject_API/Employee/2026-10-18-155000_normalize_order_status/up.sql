@@ -0,0 +1,26 @@
+-- Your SQL goes here
+-- Normalises the old free-text order statuses before 2026-10-18-160000_order_status
+-- types the column, which turns anything it does not know into pending; that is
+-- why this sorts before it. Tenants that already ran it have a typed column and
+-- nothing left to normalise, so there this does nothing.
+--
+-- The old handler added an order's stock to the inventory only when it was set
+-- to exactly 'delivered'. Those orders stay delivered and count as fully
+-- received. Any other spelling of delivered never added stock, so it becomes
+-- shipped and the stock is still to be received. 'processing', which the old
+-- front end offered, becomes approved. Other statuses lose their case and
+-- surrounding spaces.
+DO $$
+BEGIN
+    IF (SELECT udt_name FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = 'orders' AND column_name = 'status') = 'text' THEN
+        UPDATE orders
+        SET status = CASE
+            WHEN status = 'delivered' THEN 'delivered'
+            WHEN lower(btrim(status)) = 'delivered' THEN 'shipped'
+            WHEN lower(btrim(status)) = 'processing' THEN 'approved'
+            ELSE lower(btrim(status))
+        END;
+    END IF;
+END
+$$;
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE order_status_history;
+
+ALTER TABLE order_lines DROP COLUMN quantity_received;
+
+ALTER TABLE orders ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE orders ALTER COLUMN status TYPE TEXT USING status::TEXT;
+
+DROP TYPE order_status;
@@ -0,0 +1,34 @@
+-- Your SQL goes here
+CREATE TYPE order_status AS ENUM ('pending', 'approved', 'shipped', 'partially_delivered', 'delivered', 'cancelled');
+
+-- Anything the old free-text column accepted that is not a status starts over as pending
+ALTER TABLE orders
+    ALTER COLUMN status TYPE order_status USING (
+        CASE WHEN status IN ('pending', 'approved', 'shipped', 'partially_delivered', 'delivered', 'cancelled')
+             THEN status ELSE 'pending' END
+    )::order_status,
+    ALTER COLUMN status SET DEFAULT 'pending';
+
+-- Stock already added to the inventory for each line
+ALTER TABLE order_lines ADD COLUMN quantity_received INT NOT NULL DEFAULT 0;
+UPDATE order_lines
+SET quantity_received = quantity
+FROM orders
+WHERE orders.order_id = order_lines.order_id AND orders.status = 'delivered';
+
+CREATE TABLE order_status_history (
+    history_id SERIAL PRIMARY KEY,
+    order_id INT NOT NULL REFERENCES orders (order_id) ON DELETE CASCADE,
+    from_status order_status,
+    to_status order_status NOT NULL,
+    changed_by INT,
+    actor_kind TEXT,
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX order_status_history_order_id ON order_status_history (order_id);
+
+-- Earlier changes were not recorded; start each order's history at its current status
+INSERT INTO order_status_history (order_id, to_status, changed_at)
+SELECT order_id, status, COALESCE(order_date, CURRENT_TIMESTAMP)
+FROM orders
+ORDER BY order_id;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Keys made before this was recorded keep NULL: their creator may be an admin or an employee
+ALTER TABLE api_keys ADD COLUMN created_by_kind TEXT;
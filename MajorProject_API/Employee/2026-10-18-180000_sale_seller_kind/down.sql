@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE sales DROP COLUMN sold_by_kind;
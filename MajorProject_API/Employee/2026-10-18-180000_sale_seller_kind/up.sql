@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Sales made before this was recorded keep NULL: their seller may be an admin, an employee or an API key
+ALTER TABLE sales ADD COLUMN sold_by_kind TEXT;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users
+    DROP COLUMN totp_secret,
+    DROP COLUMN totp_enabled,
+    DROP COLUMN totp_last_used_step,
+    DROP COLUMN totp_recovery_codes;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN totp_secret TEXT,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_used_step BIGINT,
+    ADD COLUMN totp_recovery_codes TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_email_active_idx;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE users DROP COLUMN deleted_at;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+
+-- A closed company's email can sign up again
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_email_active_idx ON users (email) WHERE deleted_at IS NULL;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- 'database': the tenant has its own database named database_name
+-- 'schema': the tenant is the schema database_name inside the shared tenant database
+ALTER TABLE users
+    ADD COLUMN tenancy TEXT NOT NULL DEFAULT 'database'
+        CHECK (tenancy IN ('database', 'schema'));
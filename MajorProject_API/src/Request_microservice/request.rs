@@ -1,7 +1,13 @@
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
-use crate::connect_sql::sql_handler::{DbError, LogInUser};
-use reqwest::Client;
+use actix_web::{web, HttpResponse};
+use crate::auth::extractor::AuthContext;
+use crate::config::settings::Settings;
+use crate::error::app_error::AppError;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::metrics::registry::observe_service;
+use crate::telemetry::trace_context;
+use tracing::debug;
 
 #[derive(Deserialize, Debug,Serialize)]
 struct ApiResponse {
@@ -52,264 +58,181 @@ pub struct genAI_request{
     pub message : Option<String>,
 }
 
-pub async fn analytics_data(req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
+// Continue the caller's trace in the FastAPI services
+fn traced(request: RequestBuilder) -> RequestBuilder {
+    match trace_context::traceparent() {
+        Some(traceparent) => request.header("traceparent", traceparent),
+        None => request,
+    }
+}
+
+fn outcome<T, E>(parsed: &Result<T, E>) -> &'static str {
+    if parsed.is_ok() { "ok" } else { "bad_response" }
+}
+
+pub async fn analytics_data(settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
 
     // Create HTTP client
     let client = Client::new();
 
     // Build request URL with query param if needed or use POST with body if needed
-    let url = format!("http://127.0.0.1:8000/analytics_data/{}", user_db);
-    println!("analytics_data: {}", url);
+    let url = format!("{}/analytics_data/{}", settings.services.analytics_url, user_db);
+    debug!(%url, "analytics_data");
 
     // Send GET request to FastAPI
-    let resp_result = client.get(&url).send().await;
+    let started = Instant::now();
+    let resp_result = traced(client.get(&url)).send().await;
 
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
+    let resp = resp_result.map_err(|err| {
+        observe_service("analytics", "analytics_data", "unreachable", started);
+        AppError::upstream("analytics", err)
+    })?;
 
     // Parse JSON response from FastAPI
     let api_response = resp.json::<AnalyticsData>().await;
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("analytics", "analytics_data", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("analytics", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
 
 
 
-pub async fn low_stock_count(req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
+pub async fn low_stock_count(settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
 
     // Create HTTP client
     let client = Client::new();
 
     // Build request URL with query param if needed or use POST with body if needed
-    let url = format!("http://127.0.0.1:8000/low-stock-count/{}", user_db);
-    println!("low_stock_count: {}", url);
+    let url = format!("{}/low-stock-count/{}", settings.services.analytics_url, user_db);
+    debug!(%url, "low_stock_count");
 
     // Send GET request to FastAPI
-    let resp_result = client.get(&url).send().await;
+    let started = Instant::now();
+    let resp_result = traced(client.get(&url)).send().await;
 
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
+    let resp = resp_result.map_err(|err| {
+        observe_service("analytics", "low_stock_count", "unreachable", started);
+        AppError::upstream("analytics", err)
+    })?;
 
     // Parse JSON response from FastAPI
     let api_response = resp.json::<LowStockCount>().await;
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("analytics", "low_stock_count", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("analytics", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
 
-pub async fn daily_sales_summary(req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
+pub async fn daily_sales_summary(settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
 
     // Create HTTP client
     let client = Client::new();
 
     // Build request URL with query param if needed or use POST with body if needed
-    let url = format!("http://127.0.0.1:8000/daily-sales-summary/{}", user_db);
-    println!("daily_sales_summary: {}", url);
+    let url = format!("{}/daily-sales-summary/{}", settings.services.analytics_url, user_db);
+    debug!(%url, "daily_sales_summary");
 
     // Send GET request to FastAPI
-    let resp_result = client.get(&url).send().await;
+    let started = Instant::now();
+    let resp_result = traced(client.get(&url)).send().await;
 
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
+    let resp = resp_result.map_err(|err| {
+        observe_service("analytics", "daily_sales_summary", "unreachable", started);
+        AppError::upstream("analytics", err)
+    })?;
 
     // Parse JSON response from FastAPI
     let api_response = resp.json::<Vec<DailySalesSummary>>().await;
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("analytics", "daily_sales_summary", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("analytics", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
 
 
-pub async fn category_summary(req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
+pub async fn category_summary(settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
 
     // Create HTTP client
     let client = Client::new();
 
     // Build request URL with query param if needed or use POST with body if needed
-    let url = format!("http://127.0.0.1:8000/inventory/category-summary/{}",user_db);
-    println!("category_summary: {}", url);
+    let url = format!("{}/inventory/category-summary/{}", settings.services.analytics_url, user_db);
+    debug!(%url, "category_summary");
 
     // Send GET request to FastAPI
-    let resp_result = client.get(&url).send().await;
+    let started = Instant::now();
+    let resp_result = traced(client.get(&url)).send().await;
 
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
+    let resp = resp_result.map_err(|err| {
+        observe_service("analytics", "category_summary", "unreachable", started);
+        AppError::upstream("analytics", err)
+    })?;
 
     // Parse JSON response from FastAPI
     let api_response = resp.json::<Vec<CategorySummary>>().await;
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("analytics", "category_summary", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("analytics", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
 
-pub async fn product_summary(req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
-
-    let url = format!("http://127.0.0.1:8000/sales/product-summary/{}", user_db);
-    println!("ProductSummary URL: {}", url);
+pub async fn product_summary(settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
+
+    let url = format!("{}/sales/product-summary/{}", settings.services.analytics_url, user_db);
+    debug!(%url, "product_summary");
 
     let client = Client::new();
-    let resp_result = client.get(&url).send().await;
-
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
-
-    // 👇 Debug: print the raw response body
+    let started = Instant::now();
+    let resp_result = traced(client.get(&url)).send().await;
+
+    let resp = resp_result.map_err(|err| {
+        observe_service("analytics", "product_summary", "unreachable", started);
+        AppError::upstream("analytics", err)
+    })?;
+
     let text = resp.text().await.unwrap_or_else(|e| format!("Failed to read body: {}", e));
-    println!("Raw FastAPI Response: {}", text);
 
     // 👇 Attempt to parse it
     let api_response = serde_json::from_str::<Vec<ProductSummary>>(&text);
-    println!("Parsed ProductSummary: {:?}", api_response);
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("analytics", "product_summary", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("analytics", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
 
-pub async fn gen_ai(request :web::Json<genAI_request>, req: HttpRequest) -> impl Responder {
-    let user_db = match req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok())
-    {
-        Some(user) => user.database_name,
-        None => {
-            return HttpResponse::BadRequest().json(
-                serde_json::json!({"error": "Failed to retrieve user database from cookie"}),
-            );
-        }
-    };
+pub async fn gen_ai(settings: web::Data<Settings>, request :web::Json<genAI_request>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let user_db = auth.tenant;
 
     let url = format!(
-        "http://127.0.0.1:6969/genAI/{}/{}?msg={}",
+        "{}/genAI/{}/{}?msg={}",
+        settings.services.genai_url,
         user_db,
         request.action,
         urlencoding::encode(request.message.as_deref().unwrap_or(""))
     );
-    println!("gen_ai URL: {}", url);
+    debug!(%url, "gen_ai");
 
     let client = Client::new();
-    let resp_result = client.put(&url).send().await;
-
-    let resp = match resp_result {
-        Ok(res) => res,
-        Err(err) => {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to contact FastAPI: {}", err)}),
-            );
-        }
-    };
-
-    // 👇 Debug: print the raw response body
+    let started = Instant::now();
+    let resp_result = traced(client.put(&url)).send().await;
+
+    let resp = resp_result.map_err(|err| {
+        observe_service("genai", "gen_ai", "unreachable", started);
+        AppError::upstream("genai", err)
+    })?;
+
     let text = resp.text().await.unwrap_or_else(|e| format!("Failed to read body: {}", e));
-    println!("Raw gen_ai FastAPI Response: {}", text);
 
     // 👇 Attempt to parse it
     let api_response = serde_json::from_str::<genAI>(&text);
-    println!("Parsed gen_ai: {:?}", api_response);
 
-    match api_response {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(err) => HttpResponse::InternalServerError().json(
-            serde_json::json!({"error": format!("Failed to parse FastAPI response: {}", err)}),
-        ),
-    }
+    observe_service("genai", "gen_ai", outcome(&api_response), started);
+    let data = api_response.map_err(|err| AppError::bad_upstream_response("genai", err))?;
+    Ok(HttpResponse::Ok().json(data))
 }
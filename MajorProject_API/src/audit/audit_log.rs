@@ -0,0 +1,109 @@
+use actix_web::HttpRequest;
+use chrono::{SubsecRound, Utc};
+use diesel::prelude::*;
+use serde_json::Value;
+use tracing::info;
+use crate::auth::extractor::AuthContext;
+use crate::auth::throttle::client_ip;
+use crate::audit::chain::{self, ChainedFields};
+use crate::employee_schema::logs;
+use crate::models::audit::NewAuditLog;
+
+/// Who performed an audited change. Cheap to clone into `web::block` closures.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    /// Tenant database name, for the log line written on append.
+    pub tenant: String,
+    pub kind: &'static str,
+    pub id: i32,
+    pub ip: String,
+}
+
+impl Actor {
+    pub fn new(auth: &AuthContext, req: &HttpRequest) -> Self {
+        Actor {
+            tenant: auth.tenant.clone(),
+            kind: auth.principal.kind(),
+            id: auth.principal.id(),
+            ip: client_ip(req),
+        }
+    }
+}
+
+/// One audited change, e.g. `order.status_change` on `order` 42.
+pub struct AuditEntry {
+    pub action: &'static str,
+    pub entity: &'static str,
+    pub entity_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &'static str, entity: &'static str, entity_id: impl ToString) -> Self {
+        AuditEntry {
+            action,
+            entity,
+            entity_id: Some(entity_id.to_string()),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn before(mut self, state: Value) -> Self {
+        self.before = Some(state);
+        self
+    }
+
+    pub fn after(mut self, state: Value) -> Self {
+        self.after = Some(state);
+        self
+    }
+}
+
+/// Append an audit entry to the tenant's hash-chained `logs` table.
+///
+/// Call it on the same connection, inside the same transaction, as the change
+/// it describes so the two are committed together.
+///
+/// The new chain head is logged, so a table cut short later can be told apart
+/// from one that never had the rows. A head logged by a transaction that was
+/// then rolled back is never stored.
+pub fn record(conn: &mut PgConnection, actor: &Actor, entry: AuditEntry) -> QueryResult<()> {
+    // Postgres keeps microseconds; hash exactly what will be stored
+    let timestamp = Some(Utc::now().naive_utc().trunc_subsecs(6));
+
+    conn.transaction(|conn| {
+        let prev_hash = chain::lock_head(conn)?;
+        let row_hash = chain::row_hash(chain::key(), &prev_hash, &ChainedFields {
+            activity: entry.action,
+            performed_by: actor.id,
+            timestamp,
+            actor_kind: actor.kind,
+            entity: Some(entry.entity),
+            entity_id: entry.entity_id.as_deref(),
+            before_state: entry.before.as_ref(),
+            after_state: entry.after.as_ref(),
+            ip: Some(&actor.ip),
+        });
+
+        let log_id = diesel::insert_into(logs::table)
+            .values(NewAuditLog {
+                activity: entry.action.to_string(),
+                performed_by: actor.id,
+                timestamp,
+                actor_kind: actor.kind.to_string(),
+                entity: Some(entry.entity.to_string()),
+                entity_id: entry.entity_id,
+                before_state: entry.before,
+                after_state: entry.after,
+                ip: Some(actor.ip.clone()),
+                prev_hash: Some(prev_hash),
+                row_hash: Some(row_hash.clone()),
+            })
+            .returning(logs::log_id)
+            .get_result::<i32>(conn)?;
+        info!(tenant = %actor.tenant, log_id, chain_head = %row_hash, "Audit entry appended");
+        Ok(())
+    })
+}
@@ -0,0 +1,314 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use crate::config::settings;
+use crate::employee_schema::logs;
+use crate::models::audit::AuditLogField;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `prev_hash` of the first chained row.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Arbitrary key for pg_advisory_xact_lock, paired with the schema so that
+// schema-per-tenant tenants sharing a database don't block each other
+const CHAIN_LOCK_KEY: i32 = 0x6c6f6773;
+const VERIFY_BATCH: i64 = 1000;
+
+// Read once; startup refuses an `audit.secret` shorter than 32 bytes
+static CHAIN_KEY: Lazy<Vec<u8>> = Lazy::new(|| settings::get().audit.secret.clone().into_bytes());
+
+/// The key `row_hash` is computed with on this server.
+pub fn key() -> &'static [u8] {
+    &CHAIN_KEY
+}
+
+/// The fields covered by `row_hash`, in the form they come back out of Postgres.
+pub struct ChainedFields<'a> {
+    pub activity: &'a str,
+    pub performed_by: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub actor_kind: &'a str,
+    pub entity: Option<&'a str>,
+    pub entity_id: Option<&'a str>,
+    pub before_state: Option<&'a Value>,
+    pub after_state: Option<&'a Value>,
+    pub ip: Option<&'a str>,
+}
+
+// jsonb does not keep key order, and serde_json is built with `preserve_order`
+fn sorted_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Object(entries.into_iter().map(|(k, v)| (k.clone(), sorted_keys(v))).collect())
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sorted_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// HMAC-SHA256 over the previous hash and a canonical JSON encoding of the row.
+///
+/// Keyed so that someone with write access to the table alone cannot rehash
+/// the chain after editing it. Timestamps are hashed at microsecond precision and JSON objects with sorted
+/// keys, so the hash survives the round trip through `timestamp` and `jsonb`.
+pub fn row_hash(key: &[u8], prev_hash: &str, fields: &ChainedFields) -> String {
+    let canonical = json!([
+        prev_hash,
+        fields.activity,
+        fields.performed_by,
+        fields.timestamp.map(|ts| ts.format("%Y-%m-%dT%H:%M:%S%.6f").to_string()),
+        fields.actor_kind,
+        fields.entity,
+        fields.entity_id,
+        fields.before_state.map(sorted_keys),
+        fields.after_state.map(sorted_keys),
+        fields.ip,
+    ]);
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(canonical.to_string().as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Serialise chain appends and return the hash the next row must link to.
+///
+/// Holds a transaction-scoped lock, so call it inside the transaction that
+/// inserts the row.
+pub fn lock_head(conn: &mut PgConnection) -> QueryResult<String> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext(current_schema()))")
+        .bind::<Integer, _>(CHAIN_LOCK_KEY)
+        .execute(conn)?;
+
+    let head = logs::table
+        .filter(logs::row_hash.is_not_null())
+        .order(logs::log_id.desc())
+        .select(logs::row_hash)
+        .first::<Option<String>>(conn)
+        .optional()?;
+    Ok(head.flatten().unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLink {
+    pub log_id: i32,
+    pub reason: &'static str,
+}
+
+/// The last row of the chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainHead {
+    pub log_id: i32,
+    pub row_hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainReport {
+    /// Rows that predate the chain (no hashes) at the start of the table.
+    pub unchained: u64,
+    /// Rows whose links checked out before the first break.
+    pub verified: u64,
+    pub intact: bool,
+    pub first_broken: Option<BrokenLink>,
+    /// Last verified row. Rows cut off the end leave an intact chain, so compare
+    /// it with the head last logged on append to detect truncation.
+    pub head: Option<ChainHead>,
+}
+
+/// Walk the chain in `log_id` order and stop at the first broken link.
+///
+/// An edited row fails its own hash; a deleted or reordered row breaks the
+/// `prev_hash` link of the row after it.
+pub fn verify(conn: &mut PgConnection) -> QueryResult<ChainReport> {
+    let mut report = ChainReport { unchained: 0, verified: 0, intact: true, first_broken: None, head: None };
+    let mut expected_prev: Option<String> = None;
+    let mut last_id = 0;
+
+    loop {
+        let batch = logs::table
+            .filter(logs::log_id.gt(last_id))
+            .order(logs::log_id.asc())
+            .limit(VERIFY_BATCH)
+            .load::<AuditLogField>(conn)?;
+        let Some(last) = batch.last() else { break };
+        last_id = last.log_id;
+
+        if !check_rows(key(), &batch, &mut expected_prev, &mut report) {
+            return Ok(report);
+        }
+    }
+    Ok(report)
+}
+
+// Check rows in `log_id` order after those already seen; false at the first broken link
+fn check_rows(key: &[u8], rows: &[AuditLogField], expected_prev: &mut Option<String>, report: &mut ChainReport) -> bool {
+    for row in rows {
+        if let Some(reason) = check_row(key, row, expected_prev, report) {
+            report.intact = false;
+            report.first_broken = Some(BrokenLink { log_id: row.log_id, reason });
+            return false;
+        }
+    }
+    true
+}
+
+fn chained_fields(row: &AuditLogField) -> ChainedFields<'_> {
+    ChainedFields {
+        activity: &row.activity,
+        performed_by: row.performed_by,
+        timestamp: row.timestamp,
+        actor_kind: &row.actor_kind,
+        entity: row.entity.as_deref(),
+        entity_id: row.entity_id.as_deref(),
+        before_state: row.before_state.as_ref(),
+        after_state: row.after_state.as_ref(),
+        ip: row.ip.as_deref(),
+    }
+}
+
+fn check_row(key: &[u8], row: &AuditLogField, expected_prev: &mut Option<String>, report: &mut ChainReport) -> Option<&'static str> {
+    let (prev, stored) = match (&row.prev_hash, &row.row_hash, expected_prev.as_ref()) {
+        // Legacy rows are only allowed before the chain starts
+        (None, None, None) => {
+            report.unchained += 1;
+            return None;
+        }
+        (Some(prev), Some(stored), _) => (prev, stored),
+        _ => return Some("missing hash"),
+    };
+
+    let linked = match expected_prev.as_deref() {
+        Some(expected) => prev == expected,
+        None => prev == GENESIS_HASH,
+    };
+    if !linked {
+        return Some("prev_hash does not match the previous row (row deleted, inserted or reordered)");
+    }
+
+    let computed = row_hash(key, prev, &chained_fields(row));
+    if &computed != stored {
+        return Some("row_hash does not match the row contents (row edited)");
+    }
+
+    report.verified += 1;
+    report.head = Some(ChainHead { log_id: row.log_id, row_hash: stored.clone() });
+    *expected_prev = Some(stored.clone());
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const KEY: &[u8] = b"test-audit-key-of-at-least-32-bytes";
+
+    fn row(log_id: i32) -> AuditLogField {
+        AuditLogField {
+            log_id,
+            activity: "order.create".to_string(),
+            performed_by: 7,
+            timestamp: NaiveDate::from_ymd_opt(2026, 10, 18).unwrap().and_hms_micro_opt(12, 0, log_id as u32, 123_456),
+            actor_kind: "employee".to_string(),
+            entity: Some("order".to_string()),
+            entity_id: Some(log_id.to_string()),
+            before_state: None,
+            after_state: Some(json!({ "status": "pending", "lines": [{ "quantity": 3, "product_name": "M8 bolt" }] })),
+            ip: Some("10.0.0.1".to_string()),
+            prev_hash: None,
+            row_hash: None,
+        }
+    }
+
+    // Rows 1..=count, each linked to the one before
+    fn chain(count: i32) -> Vec<AuditLogField> {
+        let mut prev = GENESIS_HASH.to_string();
+        (1..=count)
+            .map(|log_id| {
+                let mut row = row(log_id);
+                let hash = row_hash(KEY, &prev, &chained_fields(&row));
+                row.prev_hash = Some(std::mem::replace(&mut prev, hash.clone()));
+                row.row_hash = Some(hash);
+                row
+            })
+            .collect()
+    }
+
+    fn check(key: &[u8], rows: &[AuditLogField]) -> ChainReport {
+        let mut report = ChainReport { unchained: 0, verified: 0, intact: true, first_broken: None, head: None };
+        check_rows(key, rows, &mut None, &mut report);
+        report
+    }
+
+    fn broken_at(report: &ChainReport) -> Option<i32> {
+        report.first_broken.as_ref().map(|broken| broken.log_id)
+    }
+
+    #[test]
+    fn intact_chain_reports_its_head() {
+        let rows = chain(3);
+        let report = check(KEY, &rows);
+        assert!(report.intact);
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.head, Some(ChainHead { log_id: 3, row_hash: rows[2].row_hash.clone().unwrap() }));
+    }
+
+    #[test]
+    fn hash_ignores_json_key_order() {
+        let mut reordered = row(1);
+        reordered.after_state = Some(json!({ "lines": [{ "product_name": "M8 bolt", "quantity": 3 }], "status": "pending" }));
+        assert_eq!(
+            row_hash(KEY, GENESIS_HASH, &chained_fields(&row(1))),
+            row_hash(KEY, GENESIS_HASH, &chained_fields(&reordered)),
+        );
+    }
+
+    #[test]
+    fn edited_row_fails_its_own_hash() {
+        let mut rows = chain(3);
+        rows[1].performed_by = 8;
+        let report = check(KEY, &rows);
+        assert!(!report.intact);
+        assert_eq!(broken_at(&report), Some(2));
+        assert_eq!(report.verified, 1);
+    }
+
+    #[test]
+    fn deleted_row_breaks_the_next_link() {
+        let mut rows = chain(3);
+        rows.remove(1);
+        assert_eq!(broken_at(&check(KEY, &rows)), Some(3));
+    }
+
+    #[test]
+    fn rehashing_without_the_key_is_detected() {
+        assert_eq!(broken_at(&check(b"another-key-of-at-least-32-bytes!!", &chain(2))), Some(1));
+    }
+
+    #[test]
+    fn truncated_chain_stays_intact_with_an_older_head() {
+        let rows = chain(3);
+        let report = check(KEY, &rows[..2]);
+        assert!(report.intact);
+        assert_eq!(report.head.map(|head| head.log_id), Some(2));
+    }
+
+    #[test]
+    fn unchained_rows_are_only_allowed_before_the_chain() {
+        let mut rows = vec![row(0)];
+        rows.extend(chain(2));
+        let report = check(KEY, &rows);
+        assert!(report.intact);
+        assert_eq!((report.unchained, report.verified), (1, 2));
+
+        let mut rows = chain(2);
+        rows.push(row(3));
+        assert_eq!(broken_at(&check(KEY, &rows)), Some(3));
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use tracing::error;
+use crate::auth::extractor::{AuthContext, AuthError, Principal};
+use crate::auth::{permission, token};
+use crate::connect_sql::sql_handler::{establish_connection_to_user_db, DbPool};
+use crate::employee_schema::api_keys;
+use crate::schema::users;
+
+/// Every key starts with this marker so it is recognisable in logs and secret scanners.
+pub const KEY_MARKER: &str = "ik";
+/// Characters of the secret kept in clear for listing keys.
+const PREFIX_LEN: usize = 8;
+/// `last_used_at` is only rewritten once it is at least this old.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+/// A freshly minted key; `key` is shown to the caller once and never stored.
+pub struct GeneratedKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+/// Mint a key of the form `ik.<tenant>.<secret>`.
+pub fn generate(tenant: &str) -> GeneratedKey {
+    let secret = token::generate();
+    let key = format!("{}.{}.{}", KEY_MARKER, tenant, secret);
+    GeneratedKey {
+        prefix: secret[..PREFIX_LEN].to_string(),
+        hash: token::hash(&key),
+        key,
+    }
+}
+
+/// Tenant named inside a key, if it has the expected shape.
+fn tenant_of(key: &str) -> Option<&str> {
+    let rest = key.strip_prefix(KEY_MARKER)?.strip_prefix('.')?;
+    let (tenant, secret) = rest.rsplit_once('.')?;
+    (!tenant.is_empty() && !secret.is_empty()).then_some(tenant)
+}
+
+/// The `Authorization: Bearer` credential of a request, if any.
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|key| key.trim().to_string())
+}
+
+/// Resolve an API key to the tenant and scopes it was minted for, stamping
+/// `last_used_at` to within a minute.
+pub async fn authenticate(admin_pool: Option<web::Data<DbPool>>, key: String) -> Result<AuthContext, AuthError> {
+    let tenant = tenant_of(&key).ok_or(AuthError::InvalidApiKey)?.to_string();
+    let admin_pool = admin_pool.ok_or_else(|| AuthError::Internal("admin pool not configured".to_string()))?;
+
+    let result = web::block(move || -> Result<AuthContext, AuthError> {
+        // Only open a tenant pool for tenants that actually exist
+        let mut admin_conn = admin_pool.get().map_err(|e| AuthError::Internal(e.to_string()))?;
+        let known = users::table
+            .filter(users::database_name.eq(&tenant))
+            .filter(users::deleted_at.is_null())
+            .count()
+            .get_result::<i64>(&mut admin_conn)
+            .map_err(|e| AuthError::Internal(e.to_string()))?;
+        if known == 0 {
+            return Err(AuthError::InvalidApiKey);
+        }
+
+        let pool = establish_connection_to_user_db(&tenant).map_err(|e| AuthError::Internal(format!("{:?}", e)))?;
+        let mut conn = pool.get().map_err(|e| AuthError::Internal(e.to_string()))?;
+
+        let key_hash = token::hash(&key);
+        let (key_id, scopes, last_used_at) = api_keys::table
+            .filter(api_keys::key_hash.eq(&key_hash))
+            .filter(api_keys::revoked_at.is_null())
+            .select((api_keys::key_id, api_keys::permissions, api_keys::last_used_at))
+            .first::<(i32, String, Option<NaiveDateTime>)>(&mut conn)
+            .optional()
+            .map_err(|e| AuthError::Internal(e.to_string()))?
+            .ok_or(AuthError::InvalidApiKey)?;
+
+        // A busy key would otherwise write its row on every request
+        let now = Utc::now().naive_utc();
+        let stale = now - Duration::seconds(LAST_USED_RESOLUTION_SECS);
+        if last_used_at.is_none_or(|used| used < stale) {
+            diesel::update(api_keys::table.find(key_id))
+                .filter(api_keys::last_used_at.is_null().or(api_keys::last_used_at.lt(stale)))
+                .set(api_keys::last_used_at.eq(now))
+                .execute(&mut conn)
+                .map_err(|e| AuthError::Internal(e.to_string()))?;
+        }
+
+        Ok(AuthContext {
+            tenant,
+            principal: Principal::ApiKey { key_id },
+            permissions: permission::parse_list_lossy(&scopes),
+            must_change_password: false,
+            session_id: None,
+        })
+    })
+    .await;
+
+    match result {
+        Ok(auth) => auth,
+        Err(e) => {
+            error!("Thread pool error during API key lookup: {:?}", e);
+            Err(AuthError::Internal(e.to_string()))
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::future::ready;
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use futures::future::LocalBoxFuture;
+use serde_json::json;
+use tracing::{error, Span};
+use crate::auth::{api_key, session_store};
+use crate::connect_sql::sql_handler::DbPool;
+use crate::auth::permission::{self, Permission};
+use crate::auth::session::{self, PrincipalKind, SessionError};
+use crate::error::app_error;
+
+/// Who is making the request inside the tenant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Principal {
+    /// A company admin, identified by `users.user_id` in the admin database.
+    Admin { user_id: i32 },
+    /// An employee, identified by `employees.employee_id` in the tenant database.
+    Employee { employee_id: i32 },
+    /// A machine client, identified by `api_keys.key_id` in the tenant database.
+    ApiKey { key_id: i32 },
+}
+
+impl Principal {
+    pub fn id(&self) -> i32 {
+        match self {
+            Principal::Admin { user_id } => *user_id,
+            Principal::Employee { employee_id } => *employee_id,
+            Principal::ApiKey { key_id } => *key_id,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Principal::Admin { .. } => "admin",
+            Principal::Employee { .. } => "employee",
+            Principal::ApiKey { .. } => "api_key",
+        }
+    }
+
+    /// Kind and id under which this principal's sessions are tracked; API keys have none.
+    pub fn session_owner(&self) -> Option<(PrincipalKind, i32)> {
+        match self {
+            Principal::Admin { user_id } => Some((PrincipalKind::Admin, *user_id)),
+            Principal::Employee { employee_id } => Some((PrincipalKind::Employee, *employee_id)),
+            Principal::ApiKey { .. } => None,
+        }
+    }
+}
+
+/// Authenticated caller, resolved once per request from the signed session cookie
+/// or an `Authorization: Bearer` API key.
+///
+/// Taking this as a handler argument is what makes a route tenant-scoped:
+/// requests without a valid session are rejected with 401 before the handler runs.
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// Tenant database name.
+    pub tenant: String,
+    pub principal: Principal,
+    pub permissions: Vec<Permission>,
+    /// The caller still has to replace a temporary password.
+    pub must_change_password: bool,
+    /// Server-side session id; `None` for API keys.
+    pub session_id: Option<String>,
+}
+
+/// Same as [`AuthContext`], but also accepts restricted "must change password"
+/// sessions. Only the password change route should take this.
+#[derive(Debug, Clone)]
+pub struct PasswordChangeContext(pub AuthContext);
+
+#[derive(Debug)]
+pub enum AuthError {
+    Session(SessionError),
+    PasswordChangeRequired,
+    InvalidApiKey,
+    Internal(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Session(err) => err.fmt(f),
+            AuthError::PasswordChangeRequired => f.write_str("Password change required"),
+            AuthError::InvalidApiKey => f.write_str("Invalid or revoked API key"),
+            AuthError::Internal(_) => f.write_str("Authentication failed"),
+        }
+    }
+}
+
+impl AuthError {
+    /// Stable error code, see [`crate::error::app_error::AppError::code`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::Session(SessionError::Missing) => "unauthenticated",
+            AuthError::Session(SessionError::Expired) => "session_expired",
+            AuthError::Session(SessionError::Revoked) => "session_revoked",
+            AuthError::Session(SessionError::SecretMissing) | AuthError::Internal(_) => "internal_error",
+            AuthError::Session(_) => "session_invalid",
+            AuthError::PasswordChangeRequired => "password_change_required",
+            AuthError::InvalidApiKey => "invalid_api_key",
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Session(SessionError::SecretMissing) | AuthError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AuthError::Session(_) | AuthError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            AuthError::PasswordChangeRequired => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let AuthError::Internal(detail) = self {
+            error!("Authentication error: {}", detail);
+        }
+        let details = matches!(self, AuthError::PasswordChangeRequired).then(|| json!({ "must_change_password": true }));
+        app_error::respond(self.status_code(), self.code(), &self.to_string(), details)
+    }
+}
+
+impl AuthContext {
+    // Tag the request span so every log line of the request names the caller
+    fn record_on_span(&self) {
+        let span = Span::current();
+        span.record("tenant", self.tenant.as_str());
+        span.record("principal", tracing::field::display(&self.principal));
+    }
+
+    fn from_claims(claims: session::SessionClaims) -> Self {
+        let principal = match claims.kind {
+            PrincipalKind::Admin => Principal::Admin { user_id: claims.sub },
+            PrincipalKind::Employee => Principal::Employee { employee_id: claims.sub },
+        };
+        let permissions = match principal {
+            Principal::Admin { .. } => Permission::ALL.to_vec(),
+            Principal::Employee { .. } | Principal::ApiKey { .. } => permission::parse_list_lossy(&claims.role),
+        };
+
+        AuthContext {
+            tenant: claims.tenant,
+            principal,
+            permissions,
+            must_change_password: claims.must_change_password,
+            session_id: Some(claims.sid),
+        }
+    }
+}
+
+impl std::fmt::Display for Principal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.kind(), self.id())
+    }
+}
+
+impl AuthContext {
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+impl FromRequest for AuthContext {
+    type Error = AuthError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Already resolved by the permission guard in front of this route
+        if let Some(auth) = req.extensions().get::<AuthContext>() {
+            return Box::pin(ready(Ok(auth.clone())));
+        }
+        if let Some(key) = api_key::bearer_token(req) {
+            let admin_pool = req.app_data::<web::Data<DbPool>>().cloned();
+            return Box::pin(async move {
+                let auth = api_key::authenticate(admin_pool, key).await?;
+                auth.record_on_span();
+                Ok(auth)
+            });
+        }
+        let claims = session::from_request(req);
+        Box::pin(async move {
+            let auth = from_live_session(claims).await?;
+            match auth.must_change_password {
+                true => Err(AuthError::PasswordChangeRequired),
+                false => Ok(auth),
+            }
+        })
+    }
+}
+
+/// Accept a signed session only while its server-side record exists.
+async fn from_live_session(claims: Result<session::SessionClaims, SessionError>) -> Result<AuthContext, AuthError> {
+    let claims = claims.map_err(AuthError::Session)?;
+    if !session_store::is_active(&claims.sid).await {
+        return Err(AuthError::Session(SessionError::Revoked));
+    }
+    let auth = AuthContext::from_claims(claims);
+    auth.record_on_span();
+    Ok(auth)
+}
+
+impl FromRequest for PasswordChangeContext {
+    type Error = AuthError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let claims = session::from_request(req);
+        Box::pin(async move { from_live_session(claims).await.map(PasswordChangeContext) })
+    }
+}
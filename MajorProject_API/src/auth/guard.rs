@@ -0,0 +1,83 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpMessage, ResponseError};
+use crate::auth::extractor::AuthContext;
+use crate::auth::permission::Permission;
+use crate::error::app_error::AppError;
+
+/// Route middleware that requires an authenticated caller holding every listed permission.
+///
+/// Unauthenticated requests get 401, authenticated ones lacking a permission get 403.
+/// The resolved [`AuthContext`] is stored in the request extensions so the handler
+/// does not authenticate a second time.
+pub struct RequirePermission {
+    required: &'static [Permission],
+}
+
+impl RequirePermission {
+    pub fn new(required: &'static [Permission]) -> Self {
+        RequirePermission { required }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service: Rc::new(service),
+            required: self.required,
+        }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: Rc<S>,
+    required: &'static [Permission],
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let required = self.required;
+
+        Box::pin(async move {
+            let auth = match req.extract::<AuthContext>().await {
+                Ok(auth) => auth,
+                Err(err) => {
+                    let response = err.error_response();
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+            };
+
+            if let Some(missing) = required.iter().find(|p| !auth.has(**p)) {
+                let response = AppError::MissingPermission(*missing).error_response();
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            req.extensions_mut().insert(auth);
+            service.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -0,0 +1,10 @@
+pub mod session;
+pub mod extractor;
+pub mod permission;
+pub mod guard;
+pub mod password;
+pub mod token;
+pub mod throttle;
+pub mod totp;
+pub mod api_key;
+pub mod session_store;
@@ -0,0 +1,30 @@
+use bcrypt::{hash, verify, DEFAULT_COST};
+use once_cell::sync::Lazy;
+
+/// Minimum length of any password set through the API.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Password policy applied whenever a password is set or changed.
+pub fn check_policy(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err("Password must contain at least one letter and one digit".to_string());
+    }
+    Ok(())
+}
+
+// Verified against when the account does not exist, so both cases cost one bcrypt check.
+static DUMMY_HASH: Lazy<String> = Lazy::new(|| hash("not-a-real-password", DEFAULT_COST).unwrap_or_default());
+
+/// Check a password against a stored hash, spending the same time when there is none.
+pub fn verify_or_dummy(password: &str, stored_hash: Option<&str>) -> bool {
+    match stored_hash {
+        Some(stored) => verify(password, stored).unwrap_or(false),
+        None => {
+            let _ = verify(password, &DUMMY_HASH);
+            false
+        }
+    }
+}
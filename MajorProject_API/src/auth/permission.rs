@@ -0,0 +1,142 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Fine-grained capabilities an employee can be granted.
+///
+/// Stored in `employees.permission` as a comma-separated list, e.g.
+/// `"inventory.read,sales.write"`. Company admins implicitly hold all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    InventoryRead,
+    OrdersRead,
+    OrdersWrite,
+    SalesRead,
+    SalesWrite,
+    EmployeesAdmin,
+    AnalyticsRead,
+    GenAiUse,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::InventoryRead,
+        Permission::OrdersRead,
+        Permission::OrdersWrite,
+        Permission::SalesRead,
+        Permission::SalesWrite,
+        Permission::EmployeesAdmin,
+        Permission::AnalyticsRead,
+        Permission::GenAiUse,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::InventoryRead => "inventory.read",
+            Permission::OrdersRead => "orders.read",
+            Permission::OrdersWrite => "orders.write",
+            Permission::SalesRead => "sales.read",
+            Permission::SalesWrite => "sales.write",
+            Permission::EmployeesAdmin => "employees.admin",
+            Permission::AnalyticsRead => "analytics.read",
+            Permission::GenAiUse => "genai.use",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .iter()
+            .copied()
+            .find(|p| p.as_str() == s)
+            .ok_or_else(|| format!("Unknown permission `{}`", s))
+    }
+}
+
+/// Role names accepted in place of an explicit permission list.
+fn role_permissions(role: &str) -> Option<&'static [Permission]> {
+    match role {
+        "sales" => Some(&[Permission::InventoryRead, Permission::SalesRead, Permission::SalesWrite]),
+        "inventory" => Some(&[Permission::InventoryRead, Permission::OrdersRead, Permission::OrdersWrite]),
+        "manager" => Some(&Permission::ALL),
+        _ => None,
+    }
+}
+
+/// Parse an `employees.permission` value, rejecting unknown entries.
+pub fn parse_list(value: &str) -> Result<Vec<Permission>, String> {
+    let mut permissions = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match role_permissions(entry) {
+            Some(granted) => permissions.extend_from_slice(granted),
+            None => permissions.push(entry.parse()?),
+        }
+    }
+    permissions.sort_by_key(|p| p.as_str());
+    permissions.dedup();
+    Ok(permissions)
+}
+
+/// Like [`parse_list`], but silently drops entries that are no longer recognised.
+pub fn parse_list_lossy(value: &str) -> Vec<Permission> {
+    value
+        .split(',')
+        .filter_map(|entry| parse_list(entry).ok())
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_lists_are_sorted_and_deduplicated() {
+        assert_eq!(
+            parse_list(" sales.write, inventory.read,,sales.write "),
+            Ok(vec![Permission::InventoryRead, Permission::SalesWrite]),
+        );
+    }
+
+    #[test]
+    fn roles_expand_to_their_permissions() {
+        assert_eq!(
+            parse_list("sales,orders.read"),
+            Ok(vec![Permission::InventoryRead, Permission::OrdersRead, Permission::SalesRead, Permission::SalesWrite]),
+        );
+        assert_eq!(parse_list("manager").unwrap().len(), Permission::ALL.len());
+    }
+
+    #[test]
+    fn every_permission_parses_from_its_name() {
+        for permission in Permission::ALL {
+            assert_eq!(parse_list(permission.as_str()), Ok(vec![permission]));
+        }
+    }
+
+    #[test]
+    fn unknown_entries_are_rejected() {
+        let err = parse_list("sales.write,orders.delete").unwrap_err();
+        assert!(err.contains("orders.delete"), "{}", err);
+        assert!(parse_list("Sales.Write").is_err());
+    }
+
+    #[test]
+    fn empty_list_grants_nothing() {
+        assert_eq!(parse_list(""), Ok(vec![]));
+        assert_eq!(parse_list(" , "), Ok(vec![]));
+    }
+
+    #[test]
+    fn lossy_parse_drops_unknown_entries() {
+        assert_eq!(parse_list_lossy("sales.write,orders.delete"), vec![Permission::SalesWrite]);
+    }
+}
@@ -0,0 +1,228 @@
+use actix_web::cookie::{self, Cookie, SameSite};
+use actix_web::HttpRequest;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::config::settings;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Name of the cookie carrying the signed session token.
+pub const SESSION_COOKIE: &str = "Data";
+/// How long an issued session stays valid.
+pub const SESSION_TTL_HOURS: i64 = 12;
+
+const MIN_SECRET_LEN: usize = 32;
+
+// The signing key is read once; `None` means `session.secret` is missing or too short.
+static SESSION_SECRET: Lazy<Option<Vec<u8>>> = Lazy::new(|| {
+    Some(settings::get().session.secret.clone())
+        .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+        .map(String::into_bytes)
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrincipalKind {
+    Admin,
+    Employee,
+}
+
+/// Everything a tenant-scoped handler is allowed to trust about the caller.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// Session id, the key of the server-side session record.
+    pub sid: String,
+    /// Tenant database name.
+    pub tenant: String,
+    /// `users.user_id` for admins, `employees.employee_id` for employees.
+    pub sub: i32,
+    pub kind: PrincipalKind,
+    /// Admin role or employee permission string.
+    pub role: String,
+    /// Set for employees still on their temporary password; such sessions may only
+    /// be used to change it.
+    #[serde(default)]
+    pub must_change_password: bool,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Missing,
+    Malformed,
+    BadSignature,
+    Expired,
+    Revoked,
+    SecretMissing,
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            SessionError::Missing => "Missing session",
+            SessionError::Malformed => "Malformed session token",
+            SessionError::BadSignature => "Invalid session signature",
+            SessionError::Expired => "Session expired",
+            SessionError::Revoked => "Session revoked",
+            SessionError::SecretMissing => "SESSION_SECRET must be set (at least 32 bytes)",
+        };
+        f.write_str(message)
+    }
+}
+
+impl SessionClaims {
+    pub fn new(tenant: String, sub: i32, kind: PrincipalKind, role: String) -> Self {
+        let now = Utc::now();
+        SessionClaims {
+            sid: crate::auth::token::generate(),
+            tenant,
+            sub,
+            kind,
+            role,
+            must_change_password: false,
+            iat: now.timestamp(),
+            exp: (now + Duration::hours(SESSION_TTL_HOURS)).timestamp(),
+        }
+    }
+}
+
+fn mac() -> Result<HmacSha256, SessionError> {
+    let secret = SESSION_SECRET.as_ref().ok_or(SessionError::SecretMissing)?;
+    HmacSha256::new_from_slice(secret).map_err(|_| SessionError::SecretMissing)
+}
+
+/// Sign any serializable payload as `base64url(json).base64url(hmac)`.
+pub fn sign<T: Serialize>(payload: &T) -> Result<String, SessionError> {
+    let payload = serde_json::to_vec(payload).map_err(|_| SessionError::Malformed)?;
+    let payload = URL_SAFE_NO_PAD.encode(payload);
+
+    let mut mac = mac()?;
+    mac.update(payload.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    Ok(format!("{}.{}", payload, signature))
+}
+
+/// Check the signature of a token produced by [`sign`] and decode its payload.
+pub fn verify_signed<T: DeserializeOwned>(token: &str) -> Result<T, SessionError> {
+    let (payload, signature) = token.split_once('.').ok_or(SessionError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| SessionError::Malformed)?;
+
+    let mut mac = mac()?;
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| SessionError::BadSignature)?;
+
+    let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| SessionError::Malformed)?;
+    serde_json::from_slice(&payload).map_err(|_| SessionError::Malformed)
+}
+
+/// Serialize and sign session claims.
+pub fn issue(claims: &SessionClaims) -> Result<String, SessionError> {
+    sign(claims)
+}
+
+/// Check the signature and expiry of a session token and return its claims.
+pub fn verify(token: &str) -> Result<SessionClaims, SessionError> {
+    let claims: SessionClaims = verify_signed(token)?;
+    if claims.exp <= Utc::now().timestamp() {
+        return Err(SessionError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Read and validate the session cookie of an incoming request.
+pub fn from_request(req: &HttpRequest) -> Result<SessionClaims, SessionError> {
+    let cookie = req.cookie(SESSION_COOKIE).ok_or(SessionError::Missing)?;
+    verify(cookie.value())
+}
+
+/// Cookie that makes the browser drop the session.
+pub fn removal_cookie() -> Cookie<'static> {
+    let mut cookie = session_cookie(String::new());
+    cookie.make_removal();
+    cookie
+}
+
+/// Build the cookie that carries a signed session token.
+pub fn session_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::None)
+        .max_age(cookie::time::Duration::hours(SESSION_TTL_HOURS))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::{SessionSettings, Settings};
+
+    fn install_secret() {
+        settings::install(Settings {
+            session: SessionSettings { secret: "test-session-secret-of-at-least-32-bytes".to_string() },
+            ..Settings::default()
+        });
+    }
+
+    fn claims() -> SessionClaims {
+        SessionClaims::new("acme_db".to_string(), 7, PrincipalKind::Employee, "sales".to_string())
+    }
+
+    #[test]
+    fn issued_session_verifies() {
+        install_secret();
+        let claims = claims();
+        let verified = verify(&issue(&claims).unwrap()).unwrap();
+        assert_eq!((verified.tenant, verified.sub, verified.kind), (claims.tenant, 7, PrincipalKind::Employee));
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        install_secret();
+        let token = issue(&claims()).unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+
+        let mut escalated = claims();
+        escalated.kind = PrincipalKind::Admin;
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&escalated).unwrap());
+        let forged = format!("{}.{}", payload, signature);
+        assert!(matches!(verify(&forged), Err(SessionError::BadSignature)));
+    }
+
+    #[test]
+    fn signature_from_another_key_is_rejected() {
+        install_secret();
+        let token = issue(&claims()).unwrap();
+        let (payload, _) = token.split_once('.').unwrap();
+
+        let mut mac = HmacSha256::new_from_slice(b"someone-elses-secret-of-32-bytes!!").unwrap();
+        mac.update(payload.as_bytes());
+        let forged = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()));
+        assert!(matches!(verify(&forged), Err(SessionError::BadSignature)));
+    }
+
+    #[test]
+    fn malformed_token_is_rejected() {
+        install_secret();
+        assert!(matches!(verify("no-signature"), Err(SessionError::Malformed)));
+        assert!(matches!(verify("payload.!!!"), Err(SessionError::Malformed)));
+    }
+
+    #[test]
+    fn expired_session_is_rejected() {
+        install_secret();
+        let mut claims = claims();
+        claims.exp = Utc::now().timestamp() - 1;
+        let token = issue(&claims).unwrap();
+        assert!(matches!(verify(&token), Err(SessionError::Expired)));
+    }
+}
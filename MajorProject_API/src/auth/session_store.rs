@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+use actix_web::cookie::Cookie;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use chrono::Utc;
+use dashmap::DashMap;
+use tracing::warn;
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use crate::auth::session::{self, PrincipalKind, SessionClaims, SessionError};
+use crate::auth::throttle::client_ip;
+use crate::redis::redis_connection::get_redis_connection;
+
+/// Server-side record of a live session. Sessions are only valid while their
+/// record exists, so deleting it revokes the cookie immediately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub sid: String,
+    pub tenant: String,
+    pub kind: PrincipalKind,
+    pub sub: i32,
+    pub ip: String,
+    pub user_agent: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Used whenever Redis is unreachable; only sound for a single server instance.
+static FALLBACK: Lazy<DashMap<String, (SessionRecord, Instant)>> = Lazy::new(DashMap::new);
+
+fn record_key(sid: &str) -> String {
+    format!("session:{}", sid)
+}
+
+fn index_key(tenant: &str, kind: PrincipalKind, sub: i32) -> String {
+    let kind = match kind {
+        PrincipalKind::Admin => "admin",
+        PrincipalKind::Employee => "employee",
+    };
+    format!("sessions:{}:{}:{}", tenant, kind, sub)
+}
+
+fn ttl_secs(expires_at: i64) -> u64 {
+    (expires_at - Utc::now().timestamp()).max(1) as u64
+}
+
+async fn save(record: &SessionRecord) {
+    let ttl = ttl_secs(record.expires_at);
+    if let Ok(mut con) = get_redis_connection().await {
+        let index = index_key(&record.tenant, record.kind, record.sub);
+        let result: redis::RedisResult<()> = async {
+            let json = serde_json::to_string(record).unwrap_or_default();
+            con.set_ex::<_, _, ()>(record_key(&record.sid), json, ttl).await?;
+            con.sadd::<_, _, ()>(&index, &record.sid).await?;
+            // The index lives as long as the newest session in it
+            con.expire::<_, ()>(&index, ttl as i64).await?;
+            Ok(())
+        }
+        .await;
+        match result {
+            Ok(()) => return,
+            Err(e) => warn!("Redis unavailable for session store, using memory: {}", e),
+        }
+    }
+    FALLBACK.insert(record.sid.clone(), (record.clone(), Instant::now() + Duration::from_secs(ttl)));
+}
+
+async fn load(sid: &str) -> Option<SessionRecord> {
+    if let Ok(mut con) = get_redis_connection().await {
+        if let Ok(json) = con.get::<_, Option<String>>(record_key(sid)).await {
+            return json.and_then(|json| serde_json::from_str(&json).ok());
+        }
+    }
+    let entry = FALLBACK.get(sid)?;
+    (entry.1 > Instant::now()).then(|| entry.0.clone())
+}
+
+/// Issue a signed token for `claims`, remember it server-side and return its cookie.
+pub async fn start(claims: &SessionClaims, req: &HttpRequest) -> Result<Cookie<'static>, SessionError> {
+    let token = session::issue(claims)?;
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    save(&SessionRecord {
+        sid: claims.sid.clone(),
+        tenant: claims.tenant.clone(),
+        kind: claims.kind,
+        sub: claims.sub,
+        ip: client_ip(req),
+        user_agent,
+        created_at: claims.iat,
+        expires_at: claims.exp,
+    })
+    .await;
+
+    Ok(session::session_cookie(token))
+}
+
+/// Whether a session has not been revoked or expired.
+pub async fn is_active(sid: &str) -> bool {
+    load(sid).await.is_some()
+}
+
+/// Live sessions of one principal.
+pub async fn list(tenant: &str, kind: PrincipalKind, sub: i32) -> Vec<SessionRecord> {
+    let index = index_key(tenant, kind, sub);
+    if let Ok(mut con) = get_redis_connection().await {
+        if let Ok(sids) = con.smembers::<_, Vec<String>>(&index).await {
+            let mut records = Vec::new();
+            for sid in sids {
+                match load(&sid).await {
+                    Some(record) => records.push(record),
+                    // Expired: drop it from the index
+                    None => {
+                        let _ = con.srem::<_, _, ()>(&index, &sid).await;
+                    }
+                }
+            }
+            return records;
+        }
+    }
+
+    let now = Instant::now();
+    FALLBACK
+        .iter()
+        .filter(|entry| {
+            let (record, expiry) = entry.value();
+            *expiry > now && record.tenant == tenant && record.kind == kind && record.sub == sub
+        })
+        .map(|entry| entry.value().0.clone())
+        .collect()
+}
+
+/// Session record by id, if still live.
+pub async fn get(sid: &str) -> Option<SessionRecord> {
+    load(sid).await
+}
+
+/// Revoke one session.
+///
+/// Fails closed: a session saved while Redis was up can only be revoked in Redis,
+/// so an unreachable Redis is an error rather than a silent no-op.
+pub async fn revoke(sid: &str) -> redis::RedisResult<()> {
+    // Saved while Redis was down, so there is no copy in Redis
+    if FALLBACK.remove(sid).is_some() {
+        return Ok(());
+    }
+    let mut con = get_redis_connection().await?;
+    let json = con.get::<_, Option<String>>(record_key(sid)).await?;
+    if let Some(record) = json.and_then(|json| serde_json::from_str::<SessionRecord>(&json).ok()) {
+        con.srem::<_, _, ()>(index_key(&record.tenant, record.kind, record.sub), sid).await?;
+    }
+    con.del::<_, ()>(record_key(sid)).await
+}
+
+/// Revoke every session of a principal, optionally keeping one (the caller's own).
+/// Returns how many were revoked. Fails closed like [`revoke`].
+pub async fn revoke_all(tenant: &str, kind: PrincipalKind, sub: i32, keep: Option<&str>) -> redis::RedisResult<usize> {
+    let now = Instant::now();
+    let mut revoked = 0;
+    FALLBACK.retain(|sid, (record, expiry)| {
+        let matches = record.tenant == tenant && record.kind == kind && record.sub == sub && Some(sid.as_str()) != keep;
+        if matches && *expiry > now {
+            revoked += 1;
+        }
+        !matches
+    });
+
+    let mut con = get_redis_connection().await?;
+    let index = index_key(tenant, kind, sub);
+    for sid in con.smembers::<_, Vec<String>>(&index).await? {
+        if Some(sid.as_str()) == keep {
+            continue;
+        }
+        let deleted = con.del::<_, usize>(record_key(&sid)).await?;
+        con.srem::<_, _, ()>(&index, &sid).await?;
+        revoked += deleted;
+    }
+    Ok(revoked)
+}
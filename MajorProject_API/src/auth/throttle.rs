@@ -0,0 +1,203 @@
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use actix_web::{web, HttpRequest};
+use dashmap::DashMap;
+use tracing::warn;
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use crate::config::settings::Settings;
+use crate::redis::redis_connection::get_redis_connection;
+
+/// Failed attempts are counted over this window.
+pub const FAILURE_WINDOW_SECS: u64 = 15 * 60;
+/// An account is locked after this many failures inside the window.
+pub const MAX_ACCOUNT_FAILURES: u64 = 5;
+/// An IP is locked after this many failures inside the window, across all accounts.
+pub const MAX_IP_FAILURES: u64 = 20;
+/// How long a lockout lasts.
+pub const LOCKOUT_SECS: u64 = 15 * 60;
+/// Upper bound for the delay added to a failed attempt.
+const MAX_DELAY: Duration = Duration::from_secs(8);
+/// Most keys kept in memory; beyond it new counters are not started.
+const MAX_FALLBACK_ENTRIES: usize = 100_000;
+/// Expired in-memory entries are swept at most this often.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// In-memory counters and locks, used whenever Redis is unreachable.
+/// Values are (count, expiry).
+static FALLBACK: Lazy<DashMap<String, (u64, Instant)>> = Lazy::new(DashMap::new);
+static LAST_PRUNE: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+
+// Sweep expired entries now and then, then say whether `key` may be stored.
+// Must not be called while holding a FALLBACK entry.
+fn fallback_has_room(key: &str) -> bool {
+    let now = Instant::now();
+    if let Ok(mut last) = LAST_PRUNE.try_lock() {
+        if now.duration_since(*last) >= PRUNE_INTERVAL {
+            *last = now;
+            FALLBACK.retain(|_, (_, expiry)| *expiry > now);
+        }
+    }
+    FALLBACK.len() < MAX_FALLBACK_ENTRIES || FALLBACK.contains_key(key)
+}
+
+/// Identity of a login target, e.g. `admin:jane@acme.com` or `employee:<tenant>:bob@acme.com`.
+pub fn account_key(scope: &str, email: &str) -> String {
+    format!("{}:{}", scope, email.trim().to_lowercase())
+}
+
+fn failures_key(kind: &str, id: &str) -> String {
+    format!("login:fail:{}:{}", kind, id)
+}
+
+fn lock_key(kind: &str, id: &str) -> String {
+    format!("login:lock:{}:{}", kind, id)
+}
+
+async fn incr(key: &str, ttl_secs: u64) -> u64 {
+    if let Ok(mut con) = get_redis_connection().await {
+        let result: redis::RedisResult<u64> = async {
+            let count: u64 = con.incr(key, 1).await?;
+            if count == 1 {
+                con.expire::<_, ()>(key, ttl_secs as i64).await?;
+            }
+            Ok(count)
+        }
+        .await;
+        match result {
+            Ok(count) => return count,
+            Err(e) => warn!("Redis unavailable for login throttling, using memory: {}", e),
+        }
+    }
+
+    if !fallback_has_room(key) {
+        warn!("In-memory login throttle is full, not counting `{}`", key);
+        return 0;
+    }
+    let now = Instant::now();
+    let mut entry = FALLBACK
+        .entry(key.to_string())
+        .or_insert((0, now + Duration::from_secs(ttl_secs)));
+    if entry.1 <= now {
+        *entry = (0, now + Duration::from_secs(ttl_secs));
+    }
+    entry.0 += 1;
+    entry.0
+}
+
+async fn set_lock(key: &str, ttl_secs: u64) {
+    if let Ok(mut con) = get_redis_connection().await {
+        if con.set_ex::<_, _, ()>(key, 1, ttl_secs).await.is_ok() {
+            return;
+        }
+    }
+    // Locks are rare and always kept, even past the cap
+    fallback_has_room(key);
+    FALLBACK.insert(key.to_string(), (1, Instant::now() + Duration::from_secs(ttl_secs)));
+}
+
+/// Remaining lock time, if the key is locked.
+async fn lock_remaining(key: &str) -> Option<Duration> {
+    if let Ok(mut con) = get_redis_connection().await {
+        if let Ok(ttl) = con.ttl::<_, i64>(key).await {
+            return (ttl > 0).then(|| Duration::from_secs(ttl as u64));
+        }
+    }
+    let entry = FALLBACK.get(key)?;
+    entry.1.checked_duration_since(Instant::now())
+}
+
+async fn clear(keys: &[String]) {
+    if let Ok(mut con) = get_redis_connection().await {
+        let _ = con.del::<_, ()>(keys).await;
+    }
+    for key in keys {
+        FALLBACK.remove(key);
+    }
+}
+
+/// Returns how long the caller has to wait if the account or the IP is locked out.
+pub async fn check(account: &str, ip: &str) -> Result<(), Duration> {
+    for key in [lock_key("acct", account), lock_key("ip", ip)] {
+        if let Some(remaining) = lock_remaining(&key).await {
+            return Err(remaining);
+        }
+    }
+    Ok(())
+}
+
+/// Count a failed attempt, lock out when a limit is reached, and return the delay
+/// to apply before answering.
+pub async fn record_failure(account: &str, ip: &str) -> Duration {
+    let account_failures = incr(&failures_key("acct", account), FAILURE_WINDOW_SECS).await;
+    let ip_failures = incr(&failures_key("ip", ip), FAILURE_WINDOW_SECS).await;
+
+    if account_failures >= MAX_ACCOUNT_FAILURES {
+        warn!("Locking account {} after {} failed logins", account, account_failures);
+        set_lock(&lock_key("acct", account), LOCKOUT_SECS).await;
+    }
+    if ip_failures >= MAX_IP_FAILURES {
+        warn!("Locking IP {} after {} failed logins", ip, ip_failures);
+        set_lock(&lock_key("ip", ip), LOCKOUT_SECS).await;
+    }
+
+    // 0.5s, 1s, 2s, 4s, 8s, 8s, ...
+    let exponent = account_failures.saturating_sub(1).min(5) as u32;
+    (Duration::from_millis(500) * 2u32.pow(exponent)).min(MAX_DELAY)
+}
+
+/// Count one use of a rate-limited action, e.g. `reset` by an email or an IP,
+/// and refuse it once more than `limit` were made inside `window_secs`.
+pub async fn limit(action: &str, id: &str, limit: u64, window_secs: u64) -> Result<(), Duration> {
+    let key = format!("rate:{}:{}", action, id);
+    if incr(&key, window_secs).await <= limit {
+        return Ok(());
+    }
+    Err(lock_remaining(&key).await.unwrap_or(Duration::from_secs(window_secs)))
+}
+
+/// Forget an account's failures after a successful login.
+pub async fn record_success(account: &str) {
+    clear(&[failures_key("acct", account)]).await;
+}
+
+/// Lift an account lockout and reset its counter.
+pub async fn unlock(account: &str) {
+    clear(&[failures_key("acct", account), lock_key("acct", account)]).await;
+}
+
+/// Client address used for per-IP counters and the audit log.
+///
+/// The TCP peer, unless it is one of `server.trusted_proxies`; then the nearest
+/// `X-Forwarded-For` hop that is not itself a trusted proxy. Headers from
+/// anyone else are ignored, since a client can put anything in them.
+pub fn client_ip(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr().map(|addr| addr.ip()) else {
+        return "unknown".to_string();
+    };
+    let trusted = req
+        .app_data::<web::Data<Settings>>()
+        .map(|settings| settings.server.trusted_proxies.as_slice())
+        .unwrap_or_default();
+
+    let mut client = peer;
+    if trusted.contains(&peer) {
+        // Each proxy appends the address it got the request from, so read right to left
+        let hops: Vec<&str> = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        for hop in hops.into_iter().rev() {
+            let Ok(ip) = hop.parse::<IpAddr>() else { break };
+            client = ip;
+            if !trusted.contains(&ip) {
+                break;
+            }
+        }
+    }
+    client.to_string()
+}
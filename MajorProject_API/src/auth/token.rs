@@ -0,0 +1,18 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Generate a random 256-bit secret, hex encoded.
+pub fn generate() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Hash a secret for storage. Only the hash is ever written to the database.
+pub fn hash(secret: &str) -> String {
+    to_hex(&Sha256::digest(secret.as_bytes()))
+}
@@ -0,0 +1,49 @@
+use crate::connect_sql::sql_handler::establish_connection;
+use crate::tenant::migrate::{all_tenants, migrate_tenants};
+
+/// `migrate-tenants [--concurrency N] [TENANT_DB...]`; all tenants by default,
+/// `default_concurrency` (`tenancy.migration_concurrency`) unless overridden.
+/// Exit code 0 if every tenant is up to date, 1 if any failed, 2 on bad usage.
+pub async fn run(args: &[String], default_concurrency: usize) -> i32 {
+    let mut concurrency = default_concurrency;
+    let mut tenants = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--concurrency" {
+            match args.next().and_then(|n| n.parse().ok()).filter(|&n: &usize| n > 0) {
+                Some(n) => concurrency = n,
+                None => {
+                    eprintln!("--concurrency needs a positive number");
+                    return 2;
+                }
+            }
+        } else {
+            tenants.push(arg.clone());
+        }
+    }
+
+    if tenants.is_empty() {
+        tenants = match establish_connection().and_then(|pool| all_tenants(&pool)) {
+            Ok(tenants) => tenants,
+            Err(err) => {
+                eprintln!("Failed to list tenants: {}", err);
+                return 2;
+            }
+        };
+    }
+
+    let results = migrate_tenants(tenants, concurrency).await;
+    let mut failed = 0;
+    for result in &results {
+        match &result.error {
+            Some(e) => {
+                failed += 1;
+                println!("{}: FAILED: {}", result.tenant, e);
+            }
+            None if result.applied.is_empty() => println!("{}: up to date", result.tenant),
+            None => println!("{}: applied {}", result.tenant, result.applied.join(", ")),
+        }
+    }
+    println!("{} tenants, {} failed", results.len(), failed);
+    if failed > 0 { 1 } else { 0 }
+}
@@ -0,0 +1,31 @@
+pub mod migrate_tenants;
+pub mod move_tenant;
+pub mod verify_audit;
+
+use crate::config::settings::Settings;
+
+/// Commands run instead of the server, e.g. `MajorProjectAPI verify-audit acme_db`.
+/// Returns the process exit code, or None if `args` does not name a command.
+pub async fn run(args: &[String], settings: &Settings) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    match command.as_str() {
+        "migrate-tenants" => Some(migrate_tenants::run(rest, settings.tenancy.migration_concurrency).await),
+        "move-tenant" => Some(move_tenant::run(rest).await),
+        "verify-audit" => Some(verify_audit::run(rest)),
+        "help" | "--help" | "-h" => {
+            println!("Usage: MajorProjectAPI [COMMAND]");
+            println!();
+            println!("Without a command the HTTP server is started.");
+            println!();
+            println!("Commands:");
+            println!("  migrate-tenants [--concurrency N] [TENANT_DB...]  Apply pending tenant migrations (all tenants by default)");
+            println!("  move-tenant TENANT_DB --to database|schema [--drop-source]  Switch a tenant's storage");
+            println!("  verify-audit [TENANT_DB...]  Check the audit log hash chain (all tenants by default)");
+            Some(0)
+        }
+        other => {
+            eprintln!("Unknown command `{}`; try `help`", other);
+            Some(2)
+        }
+    }
+}
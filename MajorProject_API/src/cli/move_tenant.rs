@@ -0,0 +1,60 @@
+use crate::connect_sql::sql_handler::establish_connection;
+use crate::tenant::relocate::move_tenant;
+use crate::tenant::tenancy::Tenancy;
+
+const USAGE: &str = "usage: move-tenant TENANT_DB --to database|schema [--drop-source]";
+
+/// `move-tenant TENANT_DB --to database|schema [--drop-source]`.
+/// Exit code 0 on success, 1 if the move failed, 2 on bad usage.
+pub async fn run(args: &[String]) -> i32 {
+    let mut tenant = None;
+    let mut to = None;
+    let mut drop_source = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => match args.next().map(|mode| mode.parse::<Tenancy>()) {
+                Some(Ok(mode)) => to = Some(mode),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 2;
+                }
+            },
+            "--drop-source" => drop_source = true,
+            _ if tenant.is_none() => tenant = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", USAGE);
+                return 2;
+            }
+        }
+    }
+    let (Some(tenant), Some(to)) = (tenant, to) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let pool = match establish_connection() {
+        Ok(pool) => pool,
+        Err(err) => {
+            eprintln!("Failed to create DB pool: {}", err);
+            return 2;
+        }
+    };
+    match move_tenant(&pool, &tenant, to, drop_source).await {
+        Ok(()) => {
+            println!("{}: now stored as a {}", tenant, to);
+            if !drop_source {
+                println!("The old storage was kept; drop it once the move is verified.");
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: move failed: {}", tenant, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::audit::chain;
+use crate::connect_sql::sql_handler::{establish_connection, establish_connection_to_user_db};
+use crate::tenant::migrate::all_tenants;
+
+/// Exit code 0 if every chain is intact, 1 if any is broken, 2 if a tenant could not be checked.
+pub fn run(tenants: &[String]) -> i32 {
+    let tenants = if tenants.is_empty() {
+        match establish_connection().and_then(|pool| all_tenants(&pool)) {
+            Ok(tenants) => tenants,
+            Err(err) => {
+                eprintln!("Failed to list tenants: {}", err);
+                return 2;
+            }
+        }
+    } else {
+        tenants.to_vec()
+    };
+
+    let mut code = 0;
+    for tenant in tenants {
+        let report = establish_connection_to_user_db(&tenant)
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|pool| pool.get().map_err(|e| e.to_string()))
+            .and_then(|mut conn| chain::verify(&mut conn).map_err(|e| e.to_string()));
+
+        match report {
+            Ok(report) if report.intact => {
+                match &report.head {
+                    Some(head) => println!("{}: intact ({} chained, {} before the chain), head log_id {} {}", tenant, report.verified, report.unchained, head.log_id, head.row_hash),
+                    None => println!("{}: intact ({} chained, {} before the chain)", tenant, report.verified, report.unchained),
+                }
+            }
+            Ok(report) => {
+                if let Some(broken) = &report.first_broken {
+                    println!("{}: BROKEN at log_id {}: {} ({} rows verified before it)", tenant, broken.log_id, broken.reason, report.verified);
+                }
+                code = code.max(1);
+            }
+            Err(err) => {
+                eprintln!("{}: could not verify: {}", tenant, err);
+                code = 2;
+            }
+        }
+    }
+    code
+}
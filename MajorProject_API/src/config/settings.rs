@@ -0,0 +1,464 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use crate::tenant::tenancy::Tenancy;
+
+/// Everything the server reads from its environment, loaded once at startup.
+///
+/// Sources, later ones winning: built-in defaults, the TOML file named by
+/// `CONFIG_FILE` (default `config.toml`, skipped if absent), then environment
+/// variables (and `.env`) under their historical names, e.g. `DATABASE_URL`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub redis: RedisSettings,
+    pub mongo: MongoSettings,
+    pub services: ServiceSettings,
+    pub session: SessionSettings,
+    pub audit: AuditSettings,
+    pub mail: MailSettings,
+    pub auth: AuthSettings,
+    pub tenancy: TenancySettings,
+    pub pools: PoolSettings,
+    pub export: ExportSettings,
+    pub log: LogSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    pub cors_origins: Vec<String>,
+    /// Reverse proxies whose `X-Forwarded-For` is believed. Empty means the
+    /// client address is always the TCP peer.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Separate listener serving only `/metrics`, kept off the public one.
+    pub metrics_addr: SocketAddr,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            host: "127.0.0.1".to_string(),
+            port: 8090,
+            cors_origins: vec!["http://localhost:5173".to_string()],
+            trusted_proxies: Vec::new(),
+            metrics_addr: SocketAddr::from(([127, 0, 0, 1], 9464)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    /// Admin database holding `users`.
+    pub url: String,
+    /// Server URL tenant database names are appended to.
+    pub admin_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisSettings {
+    pub url: String,
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        RedisSettings { url: "redis://127.0.0.1/".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoSettings {
+    pub url: String,
+}
+
+impl Default for MongoSettings {
+    fn default() -> Self {
+        MongoSettings { url: "mongodb://localhost:27017".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceSettings {
+    /// Sales analytics FastAPI service.
+    pub analytics_url: String,
+    /// GenAI service.
+    pub genai_url: String,
+    /// Whether `/ready` fails when one of the services is down.
+    pub required: bool,
+}
+
+impl Default for ServiceSettings {
+    fn default() -> Self {
+        ServiceSettings {
+            analytics_url: "http://127.0.0.1:8000".to_string(),
+            genai_url: "http://127.0.0.1:6969".to_string(),
+            required: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSettings {
+    /// HMAC key for session tokens, at least 32 bytes.
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditSettings {
+    /// HMAC key for the audit hash chain, at least 32 bytes. Changing it breaks
+    /// verification of rows written under the old key.
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailSettings {
+    /// `log` or `smtp`.
+    pub transport: String,
+    pub log_path: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub from: Option<String>,
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        MailSettings {
+            transport: "log".to_string(),
+            log_path: "mail.log".to_string(),
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            from: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// Front-end page reset links point at; the token is appended as `?token=`.
+    pub password_reset_url: Option<String>,
+    pub totp_issuer: String,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        AuthSettings { password_reset_url: None, totp_issuer: "Smart Inventory".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenancySettings {
+    /// Storage for new companies.
+    pub mode: Tenancy,
+    pub shared_database: String,
+    pub migrate_on_start: bool,
+    pub migration_concurrency: usize,
+}
+
+impl Default for TenancySettings {
+    fn default() -> Self {
+        TenancySettings {
+            mode: Tenancy::Database,
+            shared_database: "tenants_shared".to_string(),
+            migrate_on_start: false,
+            migration_concurrency: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolSettings {
+    /// Connections per tenant pool.
+    pub max_size: u32,
+    pub min_idle: u32,
+    /// Evict a tenant's pool after this long unused.
+    pub idle_ttl_secs: u64,
+    /// Upper bound on connections across all tenant pools.
+    pub connection_budget: u32,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        PoolSettings { max_size: 5, min_idle: 0, idle_ttl_secs: 600, connection_budget: 80 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportSettings {
+    /// Where closed companies' archives are kept.
+    pub dir: String,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        ExportSettings { dir: "exports".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings {
+    /// Filter directives when `RUST_LOG` is unset, e.g. `info,diesel=warn`.
+    pub level: String,
+    /// `json` or `text`.
+    pub format: String,
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318`; needs the `otlp` feature.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            level: "info".to_string(),
+            format: "json".to_string(),
+            otlp_endpoint: None,
+            service_name: "smart-inventory-api".to_string(),
+        }
+    }
+}
+
+/// A setting that could not be loaded, named by its TOML key and environment variable.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid setting `{}`: {}", self.key, self.message)
+    }
+}
+
+fn invalid(key: &str, message: impl Into<String>) -> ConfigError {
+    ConfigError { key: key.to_string(), message: message.into() }
+}
+
+// The parsed value of an environment variable, if set
+fn parse_env<T: FromStr>(key: &str, var: &str) -> Result<Option<T>, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    match env::var(var) {
+        Ok(raw) => raw
+            .parse()
+            .map(Some)
+            .map_err(|e| invalid(&format!("{} ({})", key, var), format!("`{}`: {}", raw, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn from_env<T: FromStr>(key: &str, var: &str, slot: &mut T) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    if let Some(value) = parse_env(key, var)? {
+        *slot = value;
+    }
+    Ok(())
+}
+
+fn from_env_opt<T: FromStr>(key: &str, var: &str, slot: &mut Option<T>) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    if let Some(value) = parse_env(key, var)? {
+        *slot = Some(value);
+    }
+    Ok(())
+}
+
+fn bool_from_env(key: &str, var: &str, slot: &mut bool) -> Result<(), ConfigError> {
+    if let Ok(raw) = env::var(var) {
+        *slot = match raw.as_str() {
+            "true" | "1" => true,
+            "false" | "0" | "" => false,
+            _ => return Err(invalid(&format!("{} ({})", key, var), format!("`{}`: expected true or false", raw))),
+        };
+    }
+    Ok(())
+}
+
+fn require_scheme(key: &str, url: &str, schemes: &[&str]) -> Result<(), ConfigError> {
+    if schemes.iter().any(|scheme| url.starts_with(&format!("{}://", scheme))) {
+        Ok(())
+    } else {
+        Err(invalid(key, format!("`{}` must start with {}://", url, schemes.join("://, "))))
+    }
+}
+
+impl Settings {
+    /// Load `.env`, the config file and the environment, then validate.
+    pub fn load() -> Result<Settings, ConfigError> {
+        dotenv::dotenv().ok();
+        let path = env::var("CONFIG_FILE").unwrap_or_else(|_| "config.toml".to_string());
+        let mut settings = if Path::new(&path).exists() {
+            let raw = fs::read_to_string(&path).map_err(|e| invalid(&path, e.to_string()))?;
+            toml::from_str(&raw).map_err(|e| invalid(&path, e.to_string()))?
+        } else {
+            Settings::default()
+        };
+        settings.apply_env()?;
+        // Request paths are appended to the service URLs
+        for url in [&mut settings.services.analytics_url, &mut settings.services.genai_url] {
+            while url.ends_with('/') {
+                url.pop();
+            }
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        from_env("server.host", "HOST", &mut self.server.host)?;
+        from_env("server.port", "PORT", &mut self.server.port)?;
+        from_env("server.metrics_addr", "METRICS_ADDR", &mut self.server.metrics_addr)?;
+        if let Ok(origins) = env::var("CORS_ALLOWED_ORIGINS") {
+            self.server.cors_origins = origins.split(',').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect();
+        }
+        if let Ok(proxies) = env::var("TRUSTED_PROXIES") {
+            self.server.trusted_proxies = proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| p.parse().map_err(|e| invalid("server.trusted_proxies (TRUSTED_PROXIES)", format!("`{}`: {}", p, e))))
+                .collect::<Result<_, _>>()?;
+        }
+        from_env("database.url", "DATABASE_URL", &mut self.database.url)?;
+        from_env("database.admin_url", "DATABASE_URL_ADMIN", &mut self.database.admin_url)?;
+        from_env("redis.url", "REDIS_URL", &mut self.redis.url)?;
+        from_env("mongo.url", "MONGO_URL", &mut self.mongo.url)?;
+        from_env("services.analytics_url", "ANALYTICS_SERVICE_URL", &mut self.services.analytics_url)?;
+        from_env("services.genai_url", "GENAI_SERVICE_URL", &mut self.services.genai_url)?;
+        bool_from_env("services.required", "SERVICES_REQUIRED", &mut self.services.required)?;
+        from_env("session.secret", "SESSION_SECRET", &mut self.session.secret)?;
+        from_env("audit.secret", "AUDIT_SECRET", &mut self.audit.secret)?;
+        from_env("mail.transport", "MAIL_TRANSPORT", &mut self.mail.transport)?;
+        from_env("mail.log_path", "MAIL_LOG_PATH", &mut self.mail.log_path)?;
+        from_env_opt("mail.smtp_host", "SMTP_HOST", &mut self.mail.smtp_host)?;
+        from_env_opt("mail.smtp_port", "SMTP_PORT", &mut self.mail.smtp_port)?;
+        from_env_opt("mail.smtp_username", "SMTP_USERNAME", &mut self.mail.smtp_username)?;
+        from_env_opt("mail.smtp_password", "SMTP_PASSWORD", &mut self.mail.smtp_password)?;
+        from_env_opt("mail.from", "MAIL_FROM", &mut self.mail.from)?;
+        from_env_opt("auth.password_reset_url", "PASSWORD_RESET_URL", &mut self.auth.password_reset_url)?;
+        from_env("auth.totp_issuer", "TOTP_ISSUER", &mut self.auth.totp_issuer)?;
+        from_env("tenancy.mode", "TENANCY_MODE", &mut self.tenancy.mode)?;
+        from_env("tenancy.shared_database", "SHARED_TENANT_DATABASE", &mut self.tenancy.shared_database)?;
+        bool_from_env("tenancy.migrate_on_start", "MIGRATE_TENANTS_ON_START", &mut self.tenancy.migrate_on_start)?;
+        from_env("tenancy.migration_concurrency", "TENANT_MIGRATION_CONCURRENCY", &mut self.tenancy.migration_concurrency)?;
+        from_env("pools.max_size", "TENANT_POOL_MAX_SIZE", &mut self.pools.max_size)?;
+        from_env("pools.min_idle", "TENANT_POOL_MIN_IDLE", &mut self.pools.min_idle)?;
+        from_env("pools.idle_ttl_secs", "TENANT_POOL_IDLE_TTL_SECS", &mut self.pools.idle_ttl_secs)?;
+        from_env("pools.connection_budget", "TENANT_POOL_CONNECTION_BUDGET", &mut self.pools.connection_budget)?;
+        from_env("export.dir", "EXPORT_DIR", &mut self.export.dir)?;
+        from_env("log.format", "LOG_FORMAT", &mut self.log.format)?;
+        from_env_opt("log.otlp_endpoint", "OTEL_EXPORTER_OTLP_ENDPOINT", &mut self.log.otlp_endpoint)?;
+        from_env("log.service_name", "OTEL_SERVICE_NAME", &mut self.log.service_name)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.database.url.is_empty() {
+            return Err(invalid("database.url (DATABASE_URL)", "must be set"));
+        }
+        require_scheme("database.url (DATABASE_URL)", &self.database.url, &["postgres", "postgresql"])?;
+        if self.database.admin_url.is_empty() {
+            return Err(invalid("database.admin_url (DATABASE_URL_ADMIN)", "must be set"));
+        }
+        require_scheme("database.admin_url (DATABASE_URL_ADMIN)", &self.database.admin_url, &["postgres", "postgresql"])?;
+        if !self.database.admin_url.ends_with('/') {
+            return Err(invalid("database.admin_url (DATABASE_URL_ADMIN)", "must end with `/`; tenant database names are appended to it"));
+        }
+        require_scheme("redis.url (REDIS_URL)", &self.redis.url, &["redis", "rediss"])?;
+        require_scheme("mongo.url (MONGO_URL)", &self.mongo.url, &["mongodb", "mongodb+srv"])?;
+        require_scheme("services.analytics_url (ANALYTICS_SERVICE_URL)", &self.services.analytics_url, &["http", "https"])?;
+        require_scheme("services.genai_url (GENAI_SERVICE_URL)", &self.services.genai_url, &["http", "https"])?;
+        for origin in &self.server.cors_origins {
+            require_scheme("server.cors_origins (CORS_ALLOWED_ORIGINS)", origin, &["http", "https"])?;
+        }
+
+        if self.session.secret.len() < 32 {
+            return Err(invalid("session.secret (SESSION_SECRET)", "must be at least 32 bytes"));
+        }
+        if self.audit.secret.len() < 32 {
+            return Err(invalid("audit.secret (AUDIT_SECRET)", "must be at least 32 bytes"));
+        }
+
+        match self.mail.transport.as_str() {
+            "log" => {}
+            "smtp" => {
+                if self.mail.smtp_host.is_none() {
+                    return Err(invalid("mail.smtp_host (SMTP_HOST)", "must be set when mail.transport is `smtp`"));
+                }
+                if self.mail.from.is_none() {
+                    return Err(invalid("mail.from (MAIL_FROM)", "must be set when mail.transport is `smtp`"));
+                }
+            }
+            other => return Err(invalid("mail.transport (MAIL_TRANSPORT)", format!("`{}`: expected `log` or `smtp`", other))),
+        }
+
+        if !matches!(self.log.format.as_str(), "json" | "text") {
+            return Err(invalid("log.format (LOG_FORMAT)", format!("`{}`: expected `json` or `text`", self.log.format)));
+        }
+        if let Some(endpoint) = &self.log.otlp_endpoint {
+            require_scheme("log.otlp_endpoint (OTEL_EXPORTER_OTLP_ENDPOINT)", endpoint, &["http", "https"])?;
+        }
+
+        if self.tenancy.migration_concurrency == 0 {
+            return Err(invalid("tenancy.migration_concurrency (TENANT_MIGRATION_CONCURRENCY)", "must be at least 1"));
+        }
+        if self.pools.max_size == 0 {
+            return Err(invalid("pools.max_size (TENANT_POOL_MAX_SIZE)", "must be at least 1"));
+        }
+        if self.pools.min_idle > self.pools.max_size {
+            return Err(invalid("pools.min_idle (TENANT_POOL_MIN_IDLE)", "must not exceed pools.max_size"));
+        }
+        if self.pools.connection_budget < self.pools.max_size {
+            return Err(invalid("pools.connection_budget (TENANT_POOL_CONNECTION_BUDGET)", "must be at least pools.max_size"));
+        }
+        Ok(())
+    }
+}
+
+static SETTINGS: OnceCell<Settings> = OnceCell::new();
+
+/// Make `settings` available to code that isn't handed them.
+///
+/// Handlers take `web::Data<Settings>` and pass on what they need, so they can
+/// be tested with any settings. The global is only for what is built lazily
+/// and shared by the whole process: the Redis, Mongo and Postgres clients and
+/// pools, and the session and audit keys.
+pub fn install(settings: Settings) -> &'static Settings {
+    SETTINGS.get_or_init(|| settings)
+}
+
+/// The settings installed at startup.
+///
+/// Panics if called before `install`; `main` installs them before anything else runs.
+pub fn get() -> &'static Settings {
+    SETTINGS.get().expect("settings are installed at startup")
+}
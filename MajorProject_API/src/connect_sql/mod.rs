@@ -1,2 +1,3 @@
 pub mod sql_handler;
 pub mod no_sql;
+pub mod pool_cache;
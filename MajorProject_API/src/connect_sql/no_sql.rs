@@ -2,11 +2,14 @@ use lazy_static::lazy_static;
 use mongodb::{bson::doc, options::ClientOptions, Client, Database};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use actix_web::HttpRequest;
 use mongodb::bson::Document;
 use mongodb::options::Collation;
 use tokio::sync::OnceCell;
-use crate::connect_sql::sql_handler::{DbError, LogInUser};
+use crate::config::settings;
+use crate::connect_sql::sql_handler::DbError;
+use crate::auth::extractor::AuthContext;
+use crate::metrics::mongo::MongoCommandMetrics;
+use tracing::info;
 // #[derive(Debug, Serialize, Deserialize)]
 // pub struct InventoryItemRequest {
 //     pub item_name: String,
@@ -53,39 +56,34 @@ lazy_static! {
 pub async fn get_mongo_client() -> Arc<Client> {
     MONGO_CLIENT
         .get_or_init(|| async {
-            let uri = "mongodb://localhost:27017"; // MongoDB connection string
-            let client_options = ClientOptions::parse(uri).await.unwrap();
+            let mut client_options = ClientOptions::parse(&settings::get().mongo.url).await.unwrap();
+            client_options.command_event_handler = Some(Arc::new(MongoCommandMetrics));
             let client = Client::with_options(client_options).unwrap();
-            println!("✅ MongoDB Client Initialized");
+            info!("MongoDB client initialized");
             Arc::new(client)
         })
         .await
         .clone()
 }
 
-pub async fn get_database(db_name:String) -> Database {
+/// Create a tenant's Mongo database; Mongo only materialises it on the first write.
+pub async fn get_database(db_name: &str) -> mongodb::error::Result<Database> {
     let client = get_mongo_client().await;
-    let db = client.database(&db_name);
+    let db = client.database(db_name);
     let collection = db.collection::<mongodb::bson::Document>("init_collection");
-    let _ = collection
+    collection
         .insert_one(doc! { "initialized": true }, None)
-        .await
-        .ok(); // Ignore error if it already exists
+        .await?;
 
-    db
+    Ok(db)
 }
 
-pub async fn get_database_inventory(req: HttpRequest) -> Result<Database, Box<dyn std::error::Error>> {
+pub async fn drop_database(db_name: &str) -> mongodb::error::Result<()> {
     let client = get_mongo_client().await;
+    client.database(db_name).drop(None).await
+}
 
-    let user_db = req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok());
-
-    match user_db {
-        Some(user) => {
-            let db = client.database(&user.database_name);
-            Ok(db)
-        }
-        None => Err("Missing or invalid user data cookie".into()),
-    }
+pub async fn get_database_inventory(auth: &AuthContext) -> Result<Database, Box<dyn std::error::Error>> {
+    let client = get_mongo_client().await;
+    Ok(client.database(&auth.tenant))
 }
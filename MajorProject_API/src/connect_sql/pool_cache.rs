@@ -0,0 +1,180 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
+use tracing::{debug, info};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use crate::config::settings::{self, PoolSettings};
+use crate::connect_sql::sql_handler::{DbError, DbPool};
+use crate::tenant::tenancy::{SearchPath, TenantTarget};
+
+/// Sizing of the per-tenant pools, from the `pools` settings.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Connections per tenant.
+    pub max_size: u32,
+    /// Connections kept open per cached tenant.
+    pub min_idle: u32,
+    /// Evict a tenant's pool after this long unused.
+    pub idle_ttl: Duration,
+    /// Upper bound on connections across all tenant pools.
+    pub connection_budget: u32,
+}
+
+impl PoolConfig {
+    pub fn from_settings(pools: &PoolSettings) -> Self {
+        PoolConfig {
+            max_size: pools.max_size,
+            min_idle: pools.min_idle,
+            idle_ttl: Duration::from_secs(pools.idle_ttl_secs),
+            connection_budget: pools.connection_budget,
+        }
+    }
+
+    /// How many tenant pools fit in the budget at full size.
+    pub fn max_pools(&self) -> usize {
+        (self.connection_budget / self.max_size) as usize
+    }
+}
+
+struct CachedPool {
+    pool: Arc<DbPool>,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evicted_lru: AtomicU64,
+    evicted_idle: AtomicU64,
+    evicted_explicit: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evicted_lru: u64,
+    pub evicted_idle: u64,
+    pub evicted_explicit: u64,
+    pub pools: usize,
+    pub max_pools: usize,
+    /// Open connections across cached pools, busy or idle.
+    pub connections: u32,
+    pub idle_connections: u32,
+}
+
+static CONFIG: Lazy<PoolConfig> = Lazy::new(|| PoolConfig::from_settings(&settings::get().pools));
+static DB_POOLS: Lazy<DashMap<String, CachedPool>> = Lazy::new(DashMap::new);
+static COUNTERS: Lazy<Counters> = Lazy::new(Counters::default);
+
+fn build_pool(target: TenantTarget) -> Result<DbPool, DbError> {
+    let mut builder = Pool::builder()
+        .max_size(CONFIG.max_size)
+        .min_idle(Some(CONFIG.min_idle));
+    if let Some(schema) = target.schema {
+        builder = builder.connection_customizer(Box::new(SearchPath(schema)));
+    }
+    builder
+        .build(ConnectionManager::<PgConnection>::new(target.url))
+        .map_err(|e| DbError::ConnectionPoolError(format!("Failed to create connection pool: {}", e)))
+}
+
+// Evict least recently used pools until the cache is back within the budget.
+// Must not be called while holding a DB_POOLS entry.
+fn shrink_to_budget(keep: &str) {
+    while DB_POOLS.len() > CONFIG.max_pools() {
+        let oldest = DB_POOLS
+            .iter()
+            .filter(|entry| entry.key() != keep)
+            .min_by_key(|entry| entry.value().last_used)
+            .map(|entry| entry.key().clone());
+        match oldest {
+            Some(database) if DB_POOLS.remove(&database).is_some() => {
+                COUNTERS.evicted_lru.fetch_add(1, Ordering::Relaxed);
+                info!("Evicted least recently used pool of `{}`", database);
+            }
+            Some(_) => continue,
+            None => break,
+        }
+    }
+}
+
+/// The pool of `database`, created on first use.
+///
+/// An evicted pool stays alive until connections checked out of it are
+/// returned, so the budget can briefly be exceeded while tenants churn.
+pub fn get(database: &str, target: impl FnOnce() -> Result<TenantTarget, DbError>) -> Result<Arc<DbPool>, DbError> {
+    if let Some(mut cached) = DB_POOLS.get_mut(database) {
+        COUNTERS.hits.fetch_add(1, Ordering::Relaxed);
+        cached.last_used = Instant::now();
+        return Ok(cached.pool.clone());
+    }
+
+    COUNTERS.misses.fetch_add(1, Ordering::Relaxed);
+    // Connecting can take seconds, so it happens outside the map's shard lock.
+    // If another request cached a pool meanwhile, that one wins and ours is dropped.
+    let built = Arc::new(build_pool(target()?)?);
+    let pool = DB_POOLS
+        .entry(database.to_string())
+        .or_insert_with(|| CachedPool { pool: built, last_used: Instant::now() })
+        .pool
+        .clone();
+    shrink_to_budget(database);
+    Ok(pool)
+}
+
+/// Drop a tenant's cached pool, e.g. before its database is dropped.
+pub fn evict(database: &str) {
+    if DB_POOLS.remove(database).is_some() {
+        COUNTERS.evicted_explicit.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Drop pools nobody has used for `pools.idle_ttl_secs`.
+pub fn evict_idle() {
+    let ttl = CONFIG.idle_ttl;
+    DB_POOLS.retain(|database, cached| {
+        let keep = cached.last_used.elapsed() < ttl;
+        if !keep {
+            COUNTERS.evicted_idle.fetch_add(1, Ordering::Relaxed);
+            debug!("Evicted idle pool of `{}`", database);
+        }
+        keep
+    });
+}
+
+pub fn stats() -> PoolStats {
+    let (connections, idle_connections) = DB_POOLS.iter().fold((0, 0), |(open, idle), entry| {
+        let state = entry.value().pool.state();
+        (open + state.connections, idle + state.idle_connections)
+    });
+    PoolStats {
+        hits: COUNTERS.hits.load(Ordering::Relaxed),
+        misses: COUNTERS.misses.load(Ordering::Relaxed),
+        evicted_lru: COUNTERS.evicted_lru.load(Ordering::Relaxed),
+        evicted_idle: COUNTERS.evicted_idle.load(Ordering::Relaxed),
+        evicted_explicit: COUNTERS.evicted_explicit.load(Ordering::Relaxed),
+        pools: DB_POOLS.len(),
+        max_pools: CONFIG.max_pools(),
+        connections,
+        idle_connections,
+    }
+}
+
+/// Sweep idle pools in the background for the lifetime of the server.
+pub fn spawn_reaper() {
+    let interval = (CONFIG.idle_ttl / 4).clamp(Duration::from_secs(5), Duration::from_secs(60));
+    actix_web::rt::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            evict_idle();
+            debug!("Tenant pools: {:?}", stats());
+        }
+    });
+}
@@ -1,13 +1,46 @@
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "order_status"))]
+    pub struct OrderStatus;
+}
+
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::OrderStatus;
+
     orders (order_id) {
         order_id -> Int4,
         supplier_name -> Varchar,
-        product_id -> Array<Text>,
-        categories -> Array<Text>,
-        quantity_ordered -> Array<Int4>,
-        price -> Array<Float4>,
         order_date -> Nullable<Timestamp>,
-        status -> Varchar,
+        status -> OrderStatus,
+    }
+}
+
+diesel::table! {
+    order_lines (line_id) {
+        line_id -> Int4,
+        order_id -> Int4,
+        sku -> Nullable<Text>,
+        product_name -> Text,
+        category -> Text,
+        quantity -> Int4,
+        unit_cost -> Float8,
+        quantity_received -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::OrderStatus;
+
+    order_status_history (history_id) {
+        history_id -> Int4,
+        order_id -> Int4,
+        from_status -> Nullable<OrderStatus>,
+        to_status -> OrderStatus,
+        changed_by -> Nullable<Int4>,
+        actor_kind -> Nullable<Text>,
+        changed_at -> Timestamp,
     }
 }
 
@@ -17,19 +50,37 @@ diesel::table! {
         activity -> Varchar,
         performed_by -> Int4,
         timestamp -> Nullable<Timestamp>,
+        actor_kind -> Text,
+        entity -> Nullable<Text>,
+        entity_id -> Nullable<Text>,
+        before_state -> Nullable<Jsonb>,
+        after_state -> Nullable<Jsonb>,
+        ip -> Nullable<Text>,
+        prev_hash -> Nullable<Text>,
+        row_hash -> Nullable<Text>,
     }
 }
 
 diesel::table! {
     sales (sale_id) {
         sale_id -> Int4,
-        product_id -> Array<Text>,
-        quantity_sold -> Array<Int4>,
-        price -> Array<Float8>,
         total_price -> Float8,
         sold_by -> Int4,
         sale_date -> Nullable<Timestamp>,
-        categories -> Array<Text>,
+        sold_by_kind -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    sale_lines (line_id) {
+        line_id -> Int4,
+        sale_id -> Int4,
+        product_name -> Text,
+        category -> Text,
+        quantity -> Int4,
+        unit_price -> Float8,
+        discount -> Float8,
+        line_total -> Float8,
     }
 }
 
@@ -46,9 +97,48 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    password_reset_tokens (token_id) {
+        token_id -> Int4,
+        employee_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    api_keys (key_id) {
+        key_id -> Int4,
+        name -> Text,
+        #[max_length = 16]
+        key_prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        permissions -> Text,
+        created_by -> Int4,
+        created_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_by_kind -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(order_lines -> orders (order_id));
+diesel::joinable!(order_status_history -> orders (order_id));
+diesel::joinable!(sale_lines -> sales (sale_id));
+diesel::joinable!(password_reset_tokens -> employees (employee_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
     orders,
+    order_lines,
+    order_status_history,
     logs,
     sales,
+    sale_lines,
     employees,
+    password_reset_tokens,
+    api_keys,
 );
\ No newline at end of file
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::time::Duration;
+use actix_web::error::BlockingError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tracing::error;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+use crate::auth::extractor::AuthError;
+use crate::auth::permission::Permission;
+use crate::auth::session::SessionError;
+use crate::connect_sql::sql_handler::DbError;
+use crate::models::tools::Status;
+use crate::telemetry::trace_context;
+
+/// Body of every error answered by the API.
+///
+/// `code` is stable and meant for programs to branch on; `message` is meant for
+/// people and may change. `request_id` matches the `X-Request-Id` header and the
+/// server logs.
+#[derive(Serialize)]
+pub struct ErrorEnvelope<'a> {
+    pub code: &'a str,
+    pub message: &'a str,
+    pub details: Option<Value>,
+    pub request_id: Option<String>,
+}
+
+/// Error envelope response for the request being handled.
+pub fn respond(status: StatusCode, code: &str, message: &str, details: Option<Value>) -> HttpResponse {
+    HttpResponse::build(status).json(ErrorEnvelope {
+        code,
+        message,
+        details,
+        request_id: trace_context::current().map(|context| context.trace_id),
+    })
+}
+
+/// Error handler for the JSON, query and path extractors, so malformed input gets
+/// the envelope too.
+pub fn reject_input<E: fmt::Display>(err: E, _req: &HttpRequest) -> actix_web::Error {
+    AppError::BadRequest(err.to_string()).into()
+}
+
+/// Fallback for requests that match no route.
+pub async fn route_not_found() -> Result<HttpResponse, AppError> {
+    Err(AppError::NotFound("No such route".to_string()))
+}
+
+/// Where a FastAPI call went wrong.
+#[derive(Debug, Clone, Copy)]
+pub enum UpstreamFailure {
+    Unreachable,
+    BadResponse,
+}
+
+/// Any error a handler can answer with.
+///
+/// Client errors carry a message that is safe to show. Server errors keep their
+/// cause for the log and answer with a generic message.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    /// Input rejected field by field; `details` names the fields.
+    Validation { message: String, details: Value },
+    /// Unknown account or wrong password; the two are never told apart.
+    InvalidCredentials,
+    Unauthorized(String),
+    Forbidden(String),
+    MissingPermission(Permission),
+    NotFound(String),
+    Conflict(String),
+    /// The order's status cannot move from `from` to `to`.
+    InvalidTransition { from: Status, to: Status },
+    /// Locked out after too many failed attempts.
+    TooManyAttempts { retry_after: Duration },
+    Auth(AuthError),
+    Database(DieselError),
+    /// No database connection could be had.
+    DatabaseUnavailable(String),
+    Mongo(mongodb::error::Error),
+    Redis(redis::RedisError),
+    Upstream { service: &'static str, failure: UpstreamFailure, detail: String },
+    Hashing(bcrypt::BcryptError),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn not_found(what: &str) -> Self {
+        AppError::NotFound(format!("{} not found", what))
+    }
+
+    /// A FastAPI service could not be reached.
+    pub fn upstream(service: &'static str, err: reqwest::Error) -> Self {
+        AppError::Upstream { service, failure: UpstreamFailure::Unreachable, detail: err.to_string() }
+    }
+
+    /// A FastAPI service answered with something we could not use.
+    pub fn bad_upstream_response(service: &'static str, err: impl fmt::Display) -> Self {
+        AppError::Upstream { service, failure: UpstreamFailure::BadResponse, detail: err.to_string() }
+    }
+
+    /// Stable identifier of the kind of error, part of the API contract.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Validation { .. } => "validation_failed",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::MissingPermission(_) => "missing_permission",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::InvalidTransition { .. } => "invalid_transition",
+            AppError::TooManyAttempts { .. } => "too_many_attempts",
+            AppError::Auth(err) => err.code(),
+            AppError::Database(err) => match err {
+                DieselError::NotFound => "not_found",
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => "already_exists",
+                DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => "conflict",
+                DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => "conflict",
+                DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, _)
+                | DieselError::DatabaseError(DatabaseErrorKind::NotNullViolation, _) => "bad_request",
+                _ => "database_error",
+            },
+            AppError::DatabaseUnavailable(_) => "database_unavailable",
+            AppError::Mongo(_) => "inventory_error",
+            AppError::Redis(_) => "cache_error",
+            AppError::Upstream { failure: UpstreamFailure::Unreachable, .. } => "upstream_unavailable",
+            AppError::Upstream { failure: UpstreamFailure::BadResponse, .. } => "upstream_bad_response",
+            AppError::Hashing(_) | AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::Validation { message, .. }
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message) => message.clone(),
+            AppError::InvalidCredentials => "Invalid email or password".to_string(),
+            AppError::MissingPermission(permission) => format!("Missing permission `{}`", permission),
+            AppError::InvalidTransition { from, to } => format!("An order that is {} cannot become {}", from, to),
+            AppError::TooManyAttempts { .. } => "Too many failed attempts, try again later".to_string(),
+            AppError::Auth(err) => err.to_string(),
+            AppError::Database(err) => match err {
+                DieselError::NotFound => "Record not found".to_string(),
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => "A record with these values already exists".to_string(),
+                DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => "The record is referenced by, or refers to, a missing record".to_string(),
+                DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => "The record was changed concurrently, try again".to_string(),
+                DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, _)
+                | DieselError::DatabaseError(DatabaseErrorKind::NotNullViolation, _) => "A value is missing or out of range".to_string(),
+                _ => "Database error".to_string(),
+            },
+            AppError::DatabaseUnavailable(_) => "Database temporarily unavailable".to_string(),
+            AppError::Mongo(_) => "Inventory store error".to_string(),
+            AppError::Redis(_) => "Cache error".to_string(),
+            AppError::Upstream { service, failure: UpstreamFailure::Unreachable, .. } => format!("The {} service is unavailable", service),
+            AppError::Upstream { service, failure: UpstreamFailure::BadResponse, .. } => format!("The {} service sent an invalid response", service),
+            AppError::Hashing(_) | AppError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            AppError::Validation { details, .. } => Some(details.clone()),
+            AppError::MissingPermission(permission) => Some(json!({ "permission": permission.as_str() })),
+            AppError::InvalidTransition { from, to } => Some(json!({ "from": from, "to": to, "allowed": from.next() })),
+            AppError::TooManyAttempts { retry_after } => Some(json!({ "retry_after_secs": retry_after.as_secs().max(1) })),
+            AppError::Auth(AuthError::PasswordChangeRequired) => Some(json!({ "must_change_password": true })),
+            AppError::Database(DieselError::DatabaseError(_, info)) if self.status_code().is_client_error() => {
+                info.constraint_name().map(|constraint| json!({ "constraint": constraint }))
+            }
+            AppError::Upstream { service, .. } => Some(json!({ "service": service })),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Auth(AuthError::Internal(detail)) => write!(f, "authentication failed: {}", detail),
+            AppError::Database(err) => write!(f, "database error: {}", err),
+            AppError::DatabaseUnavailable(detail) => write!(f, "database unavailable: {}", detail),
+            AppError::Mongo(err) => write!(f, "mongo error: {}", err),
+            AppError::Redis(err) => write!(f, "redis error: {}", err),
+            AppError::Upstream { service, detail, .. } => write!(f, "{} service error: {}", service, detail),
+            AppError::Hashing(err) => write!(f, "password hashing failed: {}", err),
+            AppError::Internal(detail) => f.write_str(detail),
+            _ => f.write_str(&self.message()),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::InvalidCredentials | AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::MissingPermission(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) | AppError::InvalidTransition { .. } => StatusCode::CONFLICT,
+            AppError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Auth(err) => err.status_code(),
+            AppError::Database(err) => match err {
+                DieselError::NotFound => StatusCode::NOT_FOUND,
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)
+                | DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)
+                | DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => StatusCode::CONFLICT,
+                DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, _)
+                | DieselError::DatabaseError(DatabaseErrorKind::NotNullViolation, _) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::DatabaseUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Upstream { failure: UpstreamFailure::Unreachable, .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Upstream { failure: UpstreamFailure::BadResponse, .. } => StatusCode::BAD_GATEWAY,
+            AppError::Mongo(_) | AppError::Redis(_) | AppError::Hashing(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        // The cause of a server error stays in the log, tagged with the request id
+        if status.is_server_error() {
+            error!(code = self.code(), error = %self, "request error");
+        }
+        let mut response = respond(status, self.code(), &self.message(), self.details());
+        if let AppError::TooManyAttempts { retry_after } = self {
+            if let Ok(value) = retry_after.as_secs().max(1).to_string().parse() {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+        }
+        response
+    }
+}
+
+impl From<DieselError> for AppError {
+    fn from(err: DieselError) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for AppError {
+    fn from(err: diesel::r2d2::PoolError) -> Self {
+        AppError::DatabaseUnavailable(err.to_string())
+    }
+}
+
+impl From<DbError> for AppError {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::ConnectionPoolError(detail) => AppError::DatabaseUnavailable(detail),
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(err: mongodb::error::Error) -> Self {
+        AppError::Mongo(err)
+    }
+}
+
+impl From<redis::RedisError> for AppError {
+    fn from(err: redis::RedisError) -> Self {
+        AppError::Redis(err)
+    }
+}
+
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(err: bcrypt::BcryptError) -> Self {
+        AppError::Hashing(err)
+    }
+}
+
+impl From<BlockingError> for AppError {
+    fn from(err: BlockingError) -> Self {
+        AppError::Internal(format!("thread pool error: {}", err))
+    }
+}
+
+impl From<AuthError> for AppError {
+    fn from(err: AuthError) -> Self {
+        AppError::Auth(err)
+    }
+}
+
+// Flatten nested errors into `field`, `field.inner` and `field[0].inner` keys
+fn collect_field_errors(errors: &ValidationErrors, prefix: &str, out: &mut Map<String, Value>) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            "" => field.to_string(),
+            _ => format!("{}.{}", prefix, field),
+        };
+        match kind {
+            ValidationErrorsKind::Field(list) => {
+                for err in list {
+                    // Struct-level rules name the field they are about
+                    let key = match (field.as_ref(), err.params.get("field").and_then(Value::as_str)) {
+                        ("__all__", Some(named)) if prefix.is_empty() => named.to_string(),
+                        ("__all__", Some(named)) => format!("{}.{}", prefix, named),
+                        _ => path.clone(),
+                    };
+                    let message = err.message.as_ref().map(|m| m.to_string()).unwrap_or_else(|| describe(err));
+                    if let Value::Array(messages) = out.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+                        messages.push(Value::String(message));
+                    }
+                }
+            }
+            ValidationErrorsKind::Struct(inner) => collect_field_errors(inner, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, inner) in items {
+                    collect_field_errors(inner, &format!("{}[{}]", path, index), out);
+                }
+            }
+        }
+    }
+}
+
+// Message for the built-in rules, which carry none of their own
+fn describe(err: &ValidationError) -> String {
+    let bound = |name: &str| err.params.get(name).map(Value::to_string);
+    match err.code.as_ref() {
+        "email" => "must be a valid email address".to_string(),
+        "must_match" => "does not match".to_string(),
+        "required" => "is required".to_string(),
+        "length" | "range" => match (bound("min"), bound("max")) {
+            (Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+            (Some(min), None) => format!("must be at least {}", min),
+            (None, Some(max)) => format!("must be at most {}", max),
+            (None, None) => "is out of range".to_string(),
+        },
+        code => format!("failed the `{}` check", code),
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Map::new();
+        collect_field_errors(&errors, "", &mut fields);
+        AppError::Validation {
+            message: "Request validation failed".to_string(),
+            details: Value::Object(fields),
+        }
+    }
+}
+
+impl From<SessionError> for AppError {
+    fn from(err: SessionError) -> Self {
+        AppError::Auth(AuthError::Session(err))
+    }
+}
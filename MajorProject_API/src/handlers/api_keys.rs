@@ -0,0 +1,92 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use crate::auth::api_key;
+use crate::auth::extractor::{AuthContext, Principal};
+use crate::auth::permission::Permission;
+use crate::employee_schema::api_keys::dsl::*;
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
+use crate::handlers::employee_handler::connect_db;
+use crate::models::api_keys::{ApiKeyField, CreateApiKeyRequest, NewApiKey};
+
+fn reject_api_key_caller(auth: &AuthContext) -> Result<(), AppError> {
+    match auth.principal {
+        Principal::ApiKey { .. } => Err(AppError::Forbidden("API keys cannot manage API keys".to_string())),
+        Principal::Admin { .. } | Principal::Employee { .. } => Ok(()),
+    }
+}
+
+pub async fn create_api_key(user_request: Validated<CreateApiKeyRequest>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    reject_api_key_caller(&auth)?;
+
+    let mut scopes = Vec::new();
+    for requested in &user_request.permissions {
+        let scope: Permission = requested.parse().map_err(AppError::BadRequest)?;
+        // Nobody can hand out more than they hold
+        if !auth.has(scope) {
+            return Err(AppError::Forbidden(format!("Cannot grant `{}` without holding it", scope)));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    let scopes = scopes.iter().map(Permission::as_str).collect::<Vec<_>>().join(",");
+
+    let mut conn = connect_db(&auth).await?;
+
+    let generated = api_key::generate(&auth.tenant);
+    let new_key = NewApiKey {
+        name: user_request.name.trim().to_string(),
+        key_prefix: generated.prefix.clone(),
+        key_hash: generated.hash,
+        permissions: scopes.clone(),
+        created_by: auth.principal.id(),
+        created_by_kind: Some(auth.principal.kind().to_string()),
+        created_at: Some(Utc::now().naive_utc()),
+    };
+
+    let id = web::block(move || {
+        diesel::insert_into(api_keys)
+            .values(&new_key)
+            .returning(key_id)
+            .get_result::<i32>(&mut conn)
+    }).await??;
+
+    // The full key is only ever shown here
+    Ok(HttpResponse::Created().json(json!({
+        "key_id": id,
+        "name": user_request.name.trim(),
+        "key": generated.key,
+        "key_prefix": generated.prefix,
+        "permissions": scopes,
+    })))
+}
+
+pub async fn list_api_keys(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    reject_api_key_caller(&auth)?;
+    let mut conn = connect_db(&auth).await?;
+
+    // Never select key_hash
+    let key_list = api_keys
+        .select((key_id, name, key_prefix, permissions, created_by, created_by_kind, created_at, last_used_at, revoked_at))
+        .order(key_id.asc())
+        .load::<ApiKeyField>(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(json!({ "api_keys": key_list })))
+}
+
+pub async fn revoke_api_key(path: web::Path<i32>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    reject_api_key_caller(&auth)?;
+    let mut conn = connect_db(&auth).await?;
+
+    let revoked = diesel::update(api_keys.filter(key_id.eq(path.into_inner())).filter(revoked_at.is_null()))
+        .set(revoked_at.eq(Utc::now().naive_utc()))
+        .execute(&mut conn)?;
+
+    match revoked {
+        0 => Err(AppError::not_found("API key")),
+        _ => Ok(HttpResponse::Ok().json("API key revoked")),
+    }
+}
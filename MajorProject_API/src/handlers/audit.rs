@@ -0,0 +1,77 @@
+use actix_web::{web, HttpResponse};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use serde_json::json;
+use crate::audit::chain;
+use crate::auth::extractor::AuthContext;
+use crate::employee_schema::logs;
+use crate::error::app_error::AppError;
+use crate::handlers::employee_handler::connect_db;
+use crate::models::audit::{AuditLogField, AuditLogQuery};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+// Boxed queries can't be cloned, so the count and the page each build their own
+fn filtered(query: &AuditLogQuery) -> logs::BoxedQuery<'_, Pg> {
+    let mut filtered = logs::table.into_boxed();
+    if let Some(kind) = &query.actor_kind {
+        filtered = filtered.filter(logs::actor_kind.eq(kind));
+    }
+    if let Some(actor) = query.performed_by {
+        filtered = filtered.filter(logs::performed_by.eq(actor));
+    }
+    if let Some(action) = &query.action {
+        filtered = filtered.filter(logs::activity.eq(action));
+    }
+    if let Some(entity) = &query.entity {
+        filtered = filtered.filter(logs::entity.eq(entity));
+    }
+    if let Some(entity_id) = &query.entity_id {
+        filtered = filtered.filter(logs::entity_id.eq(entity_id));
+    }
+    if let Some(from) = query.from {
+        filtered = filtered.filter(logs::timestamp.ge(from));
+    }
+    if let Some(to) = query.to {
+        filtered = filtered.filter(logs::timestamp.lt(to));
+    }
+    filtered
+}
+
+/// Newest first, `page` starting at 1.
+pub async fn list_audit_logs(auth: AuthContext, query: web::Query<AuditLogQuery>) -> Result<HttpResponse, AppError> {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page < 1 || !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(AppError::BadRequest(format!(
+            "`page` must be at least 1 and `per_page` between 1 and {}", MAX_PER_PAGE
+        )));
+    }
+
+    let mut conn = connect_db(&auth).await?;
+
+    let (total, entries) = web::block(move || {
+        let total = filtered(&query).count().get_result::<i64>(&mut conn)?;
+        let entries = filtered(&query)
+            .order((logs::timestamp.desc(), logs::log_id.desc()))
+            .limit(per_page)
+            .offset((page - 1) * per_page)
+            .load::<AuditLogField>(&mut conn)?;
+        Ok::<_, diesel::result::Error>((total, entries))
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "page": page,
+        "per_page": per_page,
+        "total": total,
+        "entries": entries,
+    })))
+}
+
+/// Walk the tenant's audit hash chain and report the first broken link, if any.
+pub async fn verify_audit_logs(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+    let report = web::block(move || chain::verify(&mut conn)).await??;
+    Ok(HttpResponse::Ok().json(report))
+}
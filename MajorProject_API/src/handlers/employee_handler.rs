@@ -1,92 +1,59 @@
 use std::env;
 use std::string::String;
-use actix_web::{cookie, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::Utc;
-use crate::models::user_requests::{CreateEmployeeRequest, Employee, EmployeeLogInResponse, EmployeeAdminControl, EmployeeLogInRequest, LogInUser, CreateUserLogInResponse, LoginEmployee};
-use crate::connect_sql::sql_handler::{establish_connection_to_user_db, establish_connection_to_user_db_without_cookies, DbError, DbPool, LogInUser as log_in_user};
+use crate::models::user_requests::{CreateEmployeeRequest, Employee, EmployeeLogInResponse, EmployeeAdminControl, EmployeeLogInRequest, EmployeeUnlock, LogInUser, LoginEmployee};
+use crate::connect_sql::sql_handler::{establish_connection_to_user_db, DbPool};
+use crate::auth::session::{PrincipalKind, SessionClaims};
+use crate::auth::session_store;
+use crate::auth::extractor::{AuthContext, PasswordChangeContext, Principal};
+use crate::auth::password::verify_or_dummy;
+use crate::auth::throttle;
+use crate::auth::permission::Permission;
+use crate::audit::audit_log::{self as audit, Actor, AuditEntry};
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
+use crate::validation::rules;
 use crate::employee_schema::employees;
 use crate::employee_schema::employees::dsl::*;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::row::NamedRow;
-use log::error;
 use serde::Deserialize;
+use validator::Validate;
 use serde_json::{json, Value};
 use crate::employee_schema::orders::dsl::orders;
 use crate::models::tools::OrderField;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct PasswordChange {
+    #[validate(range(min = 1))]
     id : i32,
+    /// Current (or temporary) password; required when changing your own password.
+    old_password: Option<String>,
+    #[validate(custom(function = "rules::password_policy"))]
     password: String,
+    #[validate(must_match(other = "password", message = "Password mismatch"))]
     re_password: String,
 }
-pub async fn connect_db(req: &HttpRequest) -> Result<PooledConnection<ConnectionManager<PgConnection>>, HttpResponse> {
-    // Get user-specific database pool
-    let pool = match establish_connection_to_user_db(req) {
-        Ok(pool) => pool,
-        Err(err) => {
-            error!("Failed to establish DB connection: {:?}", err);
-            return Err(HttpResponse::InternalServerError().json("Failed to establish DB connection"));
-        }
-    };
-
-    // Get a database connection from the pool
-    match pool.get() {
-        Ok(conn) => Ok(conn), // Successfully got connection
-        Err(err) => {
-            error!("Failed to get DB connection: {:?}", err);
-            Err(HttpResponse::InternalServerError().json("Failed to get DB connection"))
-        }
-    }
+pub async fn connect_db(auth: &AuthContext) -> Result<PooledConnection<ConnectionManager<PgConnection>>, AppError> {
+    connect_db_another(auth.tenant.clone()).await
 }
-pub async fn connect_db_another(database_name: String) -> Result<PooledConnection<ConnectionManager<PgConnection>>, HttpResponse> {
-    // Get user-specific database pool
-    let pool = match establish_connection_to_user_db_without_cookies(database_name) {
-        Ok(pool) => pool,
-        Err(err) => {
-            error!("Failed to establish DB connection: {:?}", err);
-            return Err(HttpResponse::InternalServerError().json("Failed to establish DB connection"));
-        }
-    };
-
-    // Get a database connection from the pool
-    match pool.get() {
-        Ok(conn) => Ok(conn), // Successfully got connection
-        Err(err) => {
-            error!("Failed to get DB connection: {:?}", err);
-            Err(HttpResponse::InternalServerError().json("Failed to get DB connection"))
-        }
-    }
+pub async fn connect_db_another(database_name: String) -> Result<PooledConnection<ConnectionManager<PgConnection>>, AppError> {
+    // Get user-specific database pool, then a connection from it
+    let pool = establish_connection_to_user_db(&database_name)?;
+    Ok(pool.get()?)
 }
-pub async fn employee_add(user_request: web::Json<CreateEmployeeRequest>, req: HttpRequest) -> impl Responder {
+pub async fn employee_add(user_request: Validated<CreateEmployeeRequest>, auth: AuthContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
     // Get database connection pool
-
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => {
-            conn
-        },
-        Err(err) => {
-                return err
-        }
-    };
+    let mut conn = connect_db(&auth).await?;
 
     // Clone password for hashing
     let password_clone = user_request.password.clone();
 
     // Hash password using a blocking thread
-    let hashed_password = match web::block(move || hash(&password_clone, DEFAULT_COST)).await {
-        Ok(Ok(hash)) => hash,
-        Ok(Err(err)) => {
-            error!("Password hashing failed: {:?}", err);
-            return HttpResponse::InternalServerError().json("Password hashing failed");
-        }
-        Err(err) => {
-            error!("Thread pool error during password hashing: {:?}", err);
-            return HttpResponse::InternalServerError().json("Password hashing failed");
-        }
-    };
+    let hashed_password = web::block(move || hash(&password_clone, DEFAULT_COST)).await??;
 
     // Create new employee
     let new_employee = Employee {
@@ -100,151 +67,194 @@ pub async fn employee_add(user_request: web::Json<CreateEmployeeRequest>, req: H
     };
 
     // Insert into database using a blocking thread
+    let actor = Actor::new(&auth, &req);
     let insert_result = web::block(move || {
-        diesel::insert_into(employees::table)
-            .values(&new_employee)
-            .execute(&mut conn)
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let new_id = diesel::insert_into(employees::table)
+                .values(&new_employee)
+                .returning(employee_id)
+                .get_result::<i32>(conn)?;
+            audit::record(conn, &actor, AuditEntry::new("employee.create", "employee", new_id)
+                .after(json!({ "name": new_employee.name, "email": new_employee.email, "permission": new_employee.permission })))
+        })
     }).await;
 
-    match insert_result {
-        Ok(Ok(_)) => HttpResponse::Created().json(EmployeeLogInResponse {
-            message: "User created successfully".to_string(),
-        }),
-        Ok(Err(e)) => {
-            error!("Failed to insert user: {:?}", e);
-            HttpResponse::InternalServerError().json("Failed to create user")
-        }
-        Err(e) => {
-            error!("Thread pool error during database insert: {:?}", e);
-            HttpResponse::InternalServerError().json("Database insert failed")
-        }
-    }
+    insert_result??;
+    Ok(HttpResponse::Created().json(EmployeeLogInResponse {
+        message: "User created successfully".to_string(),
+    }))
 }
 // this function is used by admin not by employee
-pub async fn update_employee_permission( user_request: web::Json<EmployeeAdminControl>,req:HttpRequest) -> impl Responder {
-
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => {
-            conn
-        },
-        Err(err) => {
-            return err
-        }
-    };
-    let update_result = diesel::update(employees.filter(employee_id.eq(user_request.id.clone())))
-        .set(permission.eq(user_request.permission.clone()))
-        .execute(&mut conn);
-
-    match update_result {
-        Ok(rows_affected) => {
-            if rows_affected == 0 {
-                HttpResponse::NotFound().json("Employee not found")
-            } else {
-                HttpResponse::Ok().json("Permission updated successfully")
-            }
+pub async fn update_employee_permission( user_request: Validated<EmployeeAdminControl>, auth: AuthContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+    let actor = Actor::new(&auth, &req);
+    let target = user_request.id;
+    let new_permission = user_request.permission.clone();
+    let update_result = web::block(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let previous = employees
+                .filter(employee_id.eq(target))
+                .select(permission)
+                .for_update()
+                .first::<String>(conn)
+                .optional()?;
+            let Some(previous) = previous else { return Ok(false) };
+
+            diesel::update(employees.filter(employee_id.eq(target)))
+                .set(permission.eq(&new_permission))
+                .execute(conn)?;
+            audit::record(conn, &actor, AuditEntry::new("employee.permission_update", "employee", target)
+                .before(json!({ "permission": previous }))
+                .after(json!({ "permission": new_permission })))?;
+            Ok(true)
+        })
+    }).await;
+
+    match update_result?? {
+        false => Err(AppError::not_found("Employee")),
+        true => {
+            // Permissions are fixed in the session at sign-in, so the new ones apply from the next one
+            session_store::revoke_all(&auth.tenant, PrincipalKind::Employee, user_request.id, None).await?;
+            Ok(HttpResponse::Ok().json("Permission updated successfully"))
         }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to update permission"),
     }
 }
-pub async fn employee_login(pool: web::Data<DbPool>, user_request: web::Json<EmployeeLogInRequest>) -> impl Responder {
-
-
-    let mut conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(_) => return HttpResponse::InternalServerError().json("Failed to get a database connection"),
-    };
+pub async fn employee_login(pool: web::Data<DbPool>, user_request: Validated<EmployeeLogInRequest>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
 
     let result = crate::schema::users::table
         .filter(crate::schema::users::company_name.eq(user_request.company_name.clone()))
+        .filter(crate::schema::users::deleted_at.is_null())
         .first::<LogInUser>(&mut conn)
-        .optional()
-        .expect("Error loading admin data");
-
-
-    let cookie;
-    let database_name;
-    if let Some(admin_data) = result {
-        database_name =  log_in_user{
-            database_name : admin_data.database_name.clone(),
-        };
-        let cookie_data = serde_json::to_string(&database_name)
-            .expect("Failed to serialize user");
-
-        cookie = cookie::Cookie::build("Data", cookie_data)
-            .path("/")
-            .http_only(true)
-            .secure(false)
-            .finish();
-    } else {
-        return HttpResponse::InternalServerError().json(serde_json::json!({ "message": "Cookie set successfully" }));
-    }
+        .optional()?;
 
-    // Now connect to the user database
-    conn = match connect_db_another(database_name.database_name).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
+    // Unknown companies are throttled under their own scope so they answer like any other failure
+    let tenant = result.map(|admin_data| admin_data.database_name);
+    let scope = match &tenant {
+        Some(tenant) => format!("employee:{}", tenant),
+        None => format!("employee:?{}", user_request.company_name.to_lowercase()),
     };
+    let account = throttle::account_key(&scope, &user_request.email);
+    let ip = throttle::client_ip(&req);
+    throttle::check(&account, &ip).await.map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
 
+    let employee = match &tenant {
+        Some(tenant) => {
+            // Now connect to the user database
+            conn = connect_db_another(tenant.clone()).await?;
+            employees::table
+                .filter(email.eq(user_request.email.clone()))
+                .first::<LoginEmployee>(&mut conn)
+                .optional()?
+        }
+        None => None,
+    };
 
-    let result = employees::table
-        .filter(email.eq(user_request.email.clone()))
-        .first::<LoginEmployee>(&mut conn)
-        .optional()
-        .expect("Error loading employee data");
-
-    if let Some(emp) = result {
-        if verify(&user_request.password, &emp.password).unwrap_or(false) {
-            HttpResponse::Ok().cookie(cookie).json(serde_json::json!({ "message" : "login successfully" , "result":emp}))
-        } else {
-            HttpResponse::BadRequest().json(serde_json::json!({ "message": "wrong password" }))
+    let verified = verify_or_dummy(&user_request.password, employee.as_ref().map(|emp| emp.password.as_str()));
+    let (tenant, emp) = match (tenant, employee) {
+        (Some(tenant), Some(emp)) if verified => (tenant, emp),
+        _ => {
+            let delay = throttle::record_failure(&account, &ip).await;
+            tokio::time::sleep(delay).await;
+            return Err(AppError::InvalidCredentials);
         }
-    }else {
-        HttpResponse::NotFound().json("Employee not found")
-    }
+    };
+    throttle::record_success(&account).await;
+
+    // Employees on a temporary password only get a session that can change it
+    let mut claims = SessionClaims::new(tenant, emp.employee_id, PrincipalKind::Employee, emp.permission.clone());
+    claims.must_change_password = emp.first_time_password;
+    let session_cookie = session_store::start(&claims, &req).await?;
+    Ok(HttpResponse::Ok().cookie(session_cookie).json(serde_json::json!({ "message" : "login successfully" , "must_change_password": emp.first_time_password, "result":emp})))
+}
+// lets an admin lift a login lockout before it expires
+pub async fn unlock_employee(user_request: Validated<EmployeeUnlock>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
 
+    let employee_email = employees
+        .filter(employee_id.eq(user_request.id))
+        .select(email)
+        .first::<String>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::not_found("Employee"))?;
 
+    let account = throttle::account_key(&format!("employee:{}", auth.tenant), &employee_email);
+    throttle::unlock(&account).await;
+    Ok(HttpResponse::Ok().json("Employee unlocked"))
 }
-pub async fn password_change(user_request : web::Json<PasswordChange>, req:HttpRequest) -> impl Responder {
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => {
-            conn
-        },
-        Err(err) => {
-            return err
-        }
-    };
+pub async fn password_change(user_request : Validated<PasswordChange>, auth: PasswordChangeContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let PasswordChangeContext(auth) = auth;
 
-    if user_request.password != user_request.re_password{
-        return HttpResponse::InternalServerError().json("Password mismatch");
+    // Employees may only change their own password unless they manage employees;
+    // a restricted session can never touch anyone else's.
+    let own_password = auth.principal == Principal::Employee { employee_id: user_request.id };
+    if !own_password && (auth.must_change_password || !auth.has(Permission::EmployeesAdmin)) {
+        return Err(AppError::Forbidden("Cannot change another employee's password".to_string()));
     }
 
-    let hashed_password = match hash(&user_request.password, DEFAULT_COST) {
-        Ok(hash) => hash,
-        Err(_) => return HttpResponse::InternalServerError().json("Password hashing failed"),
-    };
+    let mut conn = connect_db(&auth).await?;
 
-    let update_result = diesel::update(employees.filter(employee_id.eq(user_request.id.clone())))
-        .set(password.eq(hashed_password.clone()))
-        .execute(&mut conn);
+    let target = user_request.id;
+    let (mut conn, employee) = web::block(move || {
+        let employee = employees
+            .filter(employee_id.eq(target))
+            .first::<LoginEmployee>(&mut conn)
+            .optional();
+        (conn, employee)
+    }).await?;
+    let employee = employee?.ok_or_else(|| AppError::not_found("Employee"))?;
+
+    // bcrypt is slow on purpose, so it stays off the async workers
+    let current_hash = employee.password.clone();
+    let old_password = user_request.old_password.clone().unwrap_or_default();
+    let new_password = user_request.password.clone();
+    let hashed_password = web::block(move || {
+        if own_password {
+            if !verify(&old_password, &current_hash).unwrap_or(false) {
+                return Err(AppError::BadRequest("Current password is incorrect".to_string()));
+            }
+            if old_password == new_password {
+                return Err(AppError::BadRequest("New password must differ from the current one".to_string()));
+            }
+        }
+        Ok(hash(&new_password, DEFAULT_COST)?)
+    }).await??;
 
-    match update_result {
-        Ok(rows_affected) => {
-            if rows_affected == 0 {
-                HttpResponse::NotFound().json("Employee not found")
-            } else {
-                HttpResponse::Ok().json("password updated successfully")
+    // A password set by someone else is temporary again; the audit entry never carries the hash
+    let actor = Actor::new(&auth, &req);
+    let was_temporary = employee.first_time_password;
+    let update_result = web::block(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let rows = diesel::update(employees.filter(employee_id.eq(target)))
+                .set((password.eq(hashed_password), first_time_password.eq(!own_password)))
+                .execute(conn)?;
+            if rows > 0 {
+                audit::record(conn, &actor, AuditEntry::new("employee.password_change", "employee", target)
+                    .before(json!({ "first_time_password": was_temporary }))
+                    .after(json!({ "first_time_password": !own_password })))?;
             }
+            Ok(rows)
+        })
+    }).await;
+
+    match update_result?? {
+        0 => Err(AppError::not_found("Employee")),
+        _ if own_password => {
+            // Sign out every other device and swap the (possibly restricted) session for a full one
+            session_store::revoke_all(&auth.tenant, PrincipalKind::Employee, employee.employee_id, None).await?;
+            let claims = SessionClaims::new(auth.tenant, employee.employee_id, PrincipalKind::Employee, employee.permission);
+            let cookie = session_store::start(&claims, &req).await?;
+            Ok(HttpResponse::Ok().cookie(cookie).json("password updated successfully"))
+        }
+        _ => {
+            // The employee has to sign in again with the new temporary password
+            session_store::revoke_all(&auth.tenant, PrincipalKind::Employee, employee.employee_id, None).await?;
+            Ok(HttpResponse::Ok().json("password updated successfully"))
         }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to update permission"),
     }
 }
-pub async fn show_all_employee(req: HttpRequest) -> impl Responder {
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
-    };
-    match employees.load::<LoginEmployee>(&mut conn) {
-        Ok(employees_list) => HttpResponse::Ok().json(json!({"employees_list": employees_list})),
-        Err(_) => HttpResponse::InternalServerError().body("Error retrieving orders"),
-    }
+pub async fn show_all_employee(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+    let employees_list = employees.load::<LoginEmployee>(&mut conn)?;
+    Ok(HttpResponse::Ok().json(json!({"employees_list": employees_list})))
 }
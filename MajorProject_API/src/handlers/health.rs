@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+use actix_web::{web, HttpResponse, Responder};
+use diesel::prelude::*;
+use diesel::sql_query;
+use mongodb::bson::doc;
+use serde::Serialize;
+use serde_json::json;
+use crate::config::settings::Settings;
+use crate::connect_sql::no_sql::get_mongo_client;
+use crate::connect_sql::sql_handler::DbPool;
+use crate::redis::redis_connection::get_redis_connection;
+
+/// How long one dependency may take before it is reported down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    pub name: &'static str,
+    /// `up` or `down`.
+    pub status: &'static str,
+    /// A required dependency being down makes the server not ready.
+    pub required: bool,
+    pub latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+async fn check<F>(name: &'static str, required: bool, probe: F) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    DependencyStatus {
+        name,
+        status: if result.is_ok() { "up" } else { "down" },
+        required,
+        latency_ms: started.elapsed().as_millis(),
+        error: result.err(),
+    }
+}
+
+async fn ping_postgres(pool: DbPool) -> Result<(), String> {
+    web::block(move || {
+        let mut conn = pool.get_timeout(CHECK_TIMEOUT).map_err(|e| e.to_string())?;
+        sql_query("SELECT 1").execute(&mut conn).map(|_| ()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn ping_mongo() -> Result<(), String> {
+    let client = get_mongo_client().await;
+    client
+        .database("admin")
+        .run_command(doc! { "ping": 1 }, None)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn ping_redis() -> Result<(), String> {
+    let mut conn = get_redis_connection().await.map_err(|e| e.to_string())?;
+    redis::cmd("PING").query_async::<String>(&mut conn).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+// Both FastAPI services answer GET /
+async fn ping_service(url: String) -> Result<(), String> {
+    let resp = reqwest::Client::new().get(format!("{}/", url)).send().await.map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("responded {}", resp.status()))
+    }
+}
+
+/// Liveness: the process is up and serving requests. Dependencies are not checked,
+/// so an outage elsewhere does not get the server restarted.
+pub async fn health() -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+/// Readiness: ping every dependency. 503 if a required one is down.
+pub async fn ready(pool: web::Data<DbPool>, settings: web::Data<Settings>) -> impl Responder {
+    let services_required = settings.services.required;
+    let (postgres, mongo, redis, analytics, genai) = futures::join!(
+        check("postgres", true, ping_postgres(pool.get_ref().clone())),
+        check("mongo", true, ping_mongo()),
+        check("redis", true, ping_redis()),
+        check("analytics_service", services_required, ping_service(settings.services.analytics_url.clone())),
+        check("genai_service", services_required, ping_service(settings.services.genai_url.clone())),
+    );
+    let checks = [postgres, mongo, redis, analytics, genai];
+
+    let required_down = checks.iter().any(|c| c.required && c.error.is_some());
+    let any_down = checks.iter().any(|c| c.error.is_some());
+    let status = if required_down { "unavailable" } else if any_down { "degraded" } else { "ok" };
+    let body = json!({ "status": status, "checks": checks });
+    if required_down {
+        HttpResponse::ServiceUnavailable().json(body)
+    } else {
+        HttpResponse::Ok().json(body)
+    }
+}
@@ -0,0 +1,9 @@
+use actix_web::{HttpResponse, Responder};
+use crate::metrics::registry;
+
+/// Prometheus scrape endpoint. Not authenticated, so it is only served on `server.metrics_addr`.
+pub async fn metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(registry::render())
+}
@@ -1,3 +1,10 @@
 pub mod user_handler;
 pub mod employee_handler;
 pub mod tools;
+pub mod password_reset;
+pub mod two_factor;
+pub mod api_keys;
+pub mod sessions;
+pub mod audit;
+pub mod health;
+pub mod metrics;
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use actix_web::{web, HttpRequest, HttpResponse};
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use tracing::{error, warn};
+use serde_json::json;
+use crate::auth::session::PrincipalKind;
+use crate::auth::{session_store, throttle, token};
+use crate::config::settings::Settings;
+use crate::connect_sql::sql_handler::DbPool;
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
+use crate::employee_schema::{employees, password_reset_tokens as employee_tokens};
+use crate::handlers::employee_handler::connect_db_another;
+use crate::mail::{MailMessage, MailSender};
+use crate::models::user_requests::{NewAdminResetToken, NewEmployeeResetToken, PasswordResetConfirm, PasswordResetRequest};
+use crate::schema::{password_reset_tokens as admin_tokens, users};
+
+/// How long a reset token stays usable.
+pub const RESET_TOKEN_TTL_MINUTES: i64 = 30;
+/// Reset requests are counted over this window.
+const RESET_WINDOW_SECS: u64 = 60 * 60;
+/// Reset requests allowed per email address inside the window.
+const MAX_RESETS_PER_EMAIL: u64 = 3;
+/// Reset requests allowed per IP inside the window, across all addresses.
+const MAX_RESETS_PER_IP: u64 = 20;
+/// Token redemptions allowed per IP inside the window, valid or not.
+const MAX_CONFIRMS_PER_IP: u64 = 20;
+
+/// Tenant database of a company, looked up in the admin database.
+///
+/// Company names are not unique. A name shared by several companies resolves to
+/// none, so a token is never stored in or redeemed against the wrong tenant.
+fn tenant_for_company(conn: &mut PgConnection, company: &str) -> QueryResult<Option<String>> {
+    let mut tenants = users::table
+        .filter(users::company_name.eq(company))
+        .filter(users::deleted_at.is_null())
+        .select(users::database_name)
+        .limit(2)
+        .load::<String>(conn)?;
+    if tenants.len() > 1 {
+        warn!(company, "password reset refused: several companies have this name");
+        return Ok(None);
+    }
+    Ok(tenants.pop())
+}
+
+/// [`tenant_for_company`] on a blocking thread.
+async fn find_tenant(pool: web::Data<DbPool>, company: String) -> Result<Option<String>, AppError> {
+    web::block(move || -> Result<_, AppError> {
+        let mut conn = pool.get()?;
+        Ok(tenant_for_company(&mut conn, &company)?)
+    }).await?
+}
+
+/// Store a fresh token for an admin, invalidating older ones. `None` if the email is unknown.
+fn store_admin_token(conn: &mut PgConnection, email: &str, token_hash: String, expires_at: NaiveDateTime) -> QueryResult<Option<()>> {
+    conn.transaction(|conn| {
+        let user_id = match users::table
+            .filter(users::email.eq(email))
+            .filter(users::deleted_at.is_null())
+            .select(users::user_id)
+            .first::<i32>(conn)
+            .optional()?
+        {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        diesel::update(admin_tokens::table
+            .filter(admin_tokens::user_id.eq(user_id))
+            .filter(admin_tokens::used_at.is_null()))
+            .set(admin_tokens::used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)?;
+        diesel::insert_into(admin_tokens::table)
+            .values(NewAdminResetToken { user_id, token_hash, expires_at })
+            .execute(conn)?;
+        Ok(Some(()))
+    })
+}
+
+/// Store a fresh token for an employee, invalidating older ones. `None` if the email is unknown.
+fn store_employee_token(conn: &mut PgConnection, email: &str, token_hash: String, expires_at: NaiveDateTime) -> QueryResult<Option<()>> {
+    conn.transaction(|conn| {
+        let employee_id = match employees::table
+            .filter(employees::email.eq(email))
+            .select(employees::employee_id)
+            .first::<i32>(conn)
+            .optional()?
+        {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        diesel::update(employee_tokens::table
+            .filter(employee_tokens::employee_id.eq(employee_id))
+            .filter(employee_tokens::used_at.is_null()))
+            .set(employee_tokens::used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)?;
+        diesel::insert_into(employee_tokens::table)
+            .values(NewEmployeeResetToken { employee_id, token_hash, expires_at })
+            .execute(conn)?;
+        Ok(Some(()))
+    })
+}
+
+fn invalid_token() -> AppError {
+    AppError::BadRequest("Invalid or expired token".to_string())
+}
+
+fn reset_message(to: String, secret: &str, reset_url: Option<&str>) -> MailMessage {
+    let mut body = format!(
+        "A password reset was requested for your account.\n\nReset token: {}\n",
+        secret
+    );
+    if let Some(url) = reset_url {
+        body.push_str(&format!("\nOr open: {}?token={}\n", url, secret));
+    }
+    body.push_str(&format!(
+        "\nThe token expires in {} minutes. If you did not request this, ignore this email.\n",
+        RESET_TOKEN_TTL_MINUTES
+    ));
+
+    MailMessage {
+        to,
+        subject: "Password reset".to_string(),
+        body,
+    }
+}
+
+/// Store a token for the account, if there is one, and mail it.
+async fn issue_reset_token(
+    pool: web::Data<DbPool>,
+    mailer: Arc<dyn MailSender>,
+    reset_url: Option<String>,
+    request: PasswordResetRequest,
+) -> Result<(), AppError> {
+    let secret = token::generate();
+    let token_hash = token::hash(&secret);
+    let expires_at = Utc::now().naive_utc() + Duration::minutes(RESET_TOKEN_TTL_MINUTES);
+    let email = request.email.clone();
+
+    let stored = match request.company_name {
+        None => web::block(move || -> Result<_, AppError> {
+            let mut conn = pool.get()?;
+            Ok(store_admin_token(&mut conn, &email, token_hash, expires_at)?)
+        }).await??,
+        Some(company) => {
+            let Some(tenant) = find_tenant(pool, company).await? else {
+                return Ok(());
+            };
+            let mut tenant_conn = connect_db_another(tenant).await?;
+            web::block(move || store_employee_token(&mut tenant_conn, &email, token_hash, expires_at)).await??
+        }
+    };
+
+    if stored.is_some() {
+        let message = reset_message(request.email, &secret, reset_url.as_deref());
+        web::block(move || mailer.send(&message))
+            .await?
+            .map_err(|e| AppError::Internal(format!("failed to send password reset email: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Issue a reset token and mail it. Always answers 202 so accounts cannot be enumerated.
+///
+/// The token is issued after answering, so the response takes as long whether
+/// or not the account exists.
+pub async fn request_password_reset(
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn MailSender>,
+    settings: web::Data<Settings>,
+    user_request: Validated<PasswordResetRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let Validated(request) = user_request;
+    let email = request.email.trim().to_lowercase();
+    let ip = throttle::client_ip(&req);
+    for (id, limit) in [(email.as_str(), MAX_RESETS_PER_EMAIL), (ip.as_str(), MAX_RESETS_PER_IP)] {
+        throttle::limit("reset", id, limit, RESET_WINDOW_SECS)
+            .await
+            .map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
+    }
+
+    let mailer = mailer.into_inner();
+    let reset_url = settings.auth.password_reset_url.clone();
+    tokio::spawn(async move {
+        if let Err(e) = issue_reset_token(pool, mailer, reset_url, request).await {
+            error!("Failed to issue password reset token: {}", e);
+        }
+    });
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "If the account exists, a password reset email has been sent"
+    })))
+}
+
+/// Consume a reset token and set the new password.
+///
+/// The password is only hashed once the token has been found and locked, so
+/// invalid tokens cost no bcrypt work.
+pub async fn confirm_password_reset(pool: web::Data<DbPool>, user_request: Validated<PasswordResetConfirm>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let ip = throttle::client_ip(&req);
+    throttle::limit("reset-confirm", &ip, MAX_CONFIRMS_PER_IP, RESET_WINDOW_SECS)
+        .await
+        .map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
+
+    let Validated(confirm) = user_request;
+    let token_hash = token::hash(&confirm.token);
+    let password = confirm.password;
+    let now = Utc::now().naive_utc();
+
+    let result = match confirm.company_name {
+        None => web::block(move || {
+            pool.get()?.transaction::<_, AppError, _>(|conn| {
+                let found = admin_tokens::table
+                    .filter(admin_tokens::token_hash.eq(&token_hash))
+                    .filter(admin_tokens::used_at.is_null())
+                    .filter(admin_tokens::expires_at.gt(now))
+                    .select((admin_tokens::token_id, admin_tokens::user_id))
+                    .for_update()
+                    .first::<(i32, i32)>(conn)
+                    .optional()?;
+                let Some((token_id, user_id)) = found else { return Ok(None) };
+
+                let hashed_password = hash(&password, DEFAULT_COST)?;
+                let tenant = diesel::update(users::table.find(user_id))
+                    .set(users::password.eq(&hashed_password))
+                    .returning(users::database_name)
+                    .get_result::<String>(conn)?;
+                diesel::update(admin_tokens::table.find(token_id))
+                    .set(admin_tokens::used_at.eq(now))
+                    .execute(conn)?;
+                Ok(Some((tenant, PrincipalKind::Admin, user_id)))
+            })
+        }).await??,
+        Some(company) => {
+            let Some(tenant) = find_tenant(pool, company).await? else {
+                return Err(invalid_token());
+            };
+            let mut tenant_conn = connect_db_another(tenant.clone()).await?;
+            web::block(move || {
+                tenant_conn.transaction::<_, AppError, _>(move |conn| {
+                    let found = employee_tokens::table
+                        .filter(employee_tokens::token_hash.eq(&token_hash))
+                        .filter(employee_tokens::used_at.is_null())
+                        .filter(employee_tokens::expires_at.gt(now))
+                        .select((employee_tokens::token_id, employee_tokens::employee_id))
+                        .for_update()
+                        .first::<(i32, i32)>(conn)
+                        .optional()?;
+                    let Some((token_id, employee_id)) = found else { return Ok(None) };
+
+                    // The employee chose this password, so it is no longer temporary
+                    let hashed_password = hash(&password, DEFAULT_COST)?;
+                    diesel::update(employees::table.find(employee_id))
+                        .set((employees::password.eq(&hashed_password), employees::first_time_password.eq(false)))
+                        .execute(conn)?;
+                    diesel::update(employee_tokens::table.find(token_id))
+                        .set(employee_tokens::used_at.eq(now))
+                        .execute(conn)?;
+                    Ok(Some((tenant, PrincipalKind::Employee, employee_id)))
+                })
+            }).await??
+        }
+    };
+
+    let (tenant, kind, id) = result.ok_or_else(invalid_token)?;
+    // Whoever knew the old password is signed out
+    session_store::revoke_all(&tenant, kind, id, None).await?;
+    Ok(HttpResponse::Ok().json("password updated successfully"))
+}
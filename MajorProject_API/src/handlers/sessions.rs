@@ -0,0 +1,80 @@
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+use crate::auth::extractor::{AuthContext, PasswordChangeContext};
+use crate::auth::permission::Permission;
+use crate::auth::session::{self, PrincipalKind};
+use crate::auth::session_store;
+use crate::error::app_error::AppError;
+use crate::models::user_requests::SessionQuery;
+
+/// Whose sessions a request is about: the caller's own, or an employee's when the
+/// caller manages employees.
+fn target(auth: &AuthContext, employee_id: Option<i32>) -> Result<(PrincipalKind, i32), AppError> {
+    let own = auth.principal.session_owner()
+        .ok_or_else(|| AppError::Forbidden("API keys have no sessions".to_string()))?;
+    match employee_id {
+        None => Ok(own),
+        Some(id) if own == (PrincipalKind::Employee, id) => Ok(own),
+        Some(id) if auth.has(Permission::EmployeesAdmin) => Ok((PrincipalKind::Employee, id)),
+        Some(_) => Err(AppError::Forbidden("Cannot manage another employee's sessions".to_string())),
+    }
+}
+
+/// End the current session. Also works for restricted sessions.
+pub async fn logout(auth: PasswordChangeContext) -> Result<HttpResponse, AppError> {
+    let PasswordChangeContext(auth) = auth;
+    if let Some(sid) = &auth.session_id {
+        session_store::revoke(sid).await?;
+    }
+    Ok(HttpResponse::Ok().cookie(session::removal_cookie()).json("Logged out"))
+}
+
+pub async fn list_sessions(auth: AuthContext, query: web::Query<SessionQuery>) -> Result<HttpResponse, AppError> {
+    let (kind, id) = target(&auth, query.employee_id)?;
+
+    let sessions: Vec<_> = session_store::list(&auth.tenant, kind, id)
+        .await
+        .into_iter()
+        .map(|record| json!({
+            "sid": record.sid,
+            "ip": record.ip,
+            "user_agent": record.user_agent,
+            "created_at": record.created_at,
+            "expires_at": record.expires_at,
+            "current": auth.session_id.as_deref() == Some(record.sid.as_str()),
+        }))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({ "sessions": sessions })))
+}
+
+pub async fn revoke_session(path: web::Path<String>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let sid = path.into_inner();
+    let record = match session_store::get(&sid).await {
+        Some(record) if record.tenant == auth.tenant => record,
+        _ => return Err(AppError::not_found("Session")),
+    };
+
+    let employee_id = match record.kind {
+        PrincipalKind::Employee => Some(record.sub),
+        PrincipalKind::Admin => None,
+    };
+    if target(&auth, employee_id)? != (record.kind, record.sub) {
+        return Err(AppError::Forbidden("Cannot revoke this session".to_string()));
+    }
+
+    session_store::revoke(&sid).await?;
+    Ok(HttpResponse::Ok().json("Session revoked"))
+}
+
+/// "Sign out everywhere": revoke every session of the caller, or of an employee.
+pub async fn revoke_all_sessions(auth: AuthContext, query: web::Query<SessionQuery>) -> Result<HttpResponse, AppError> {
+    let (kind, id) = target(&auth, query.employee_id)?;
+
+    let revoked = session_store::revoke_all(&auth.tenant, kind, id, None).await?;
+    let mut response = HttpResponse::Ok();
+    if auth.principal.session_owner() == Some((kind, id)) {
+        response.cookie(session::removal_cookie());
+    }
+    Ok(response.json(json!({ "revoked": revoked })))
+}
@@ -1,14 +1,15 @@
-use crate::employee_schema::orders::{categories, order_id, status};
+use crate::employee_schema::orders::{order_id, status};
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web::error::BlockingError;
 use chrono::format::Item;
 use chrono::Utc;
 use diesel::{numeric_expr, RunQueryDsl};
 use diesel::dsl::Order;
-use log::error;
+use tracing::{error, warn};
 use serde_json::json;
 use crate::employee_schema::orders::dsl::orders as Orders;
-use crate::models::tools::{OrderField, OrderInSQL, OrdersRelatedResponse, OrdersRequest, StatusChange, Status, SaleRequest, SaleInSQL, SaleRelatedResponse, SaleField};
+use crate::employee_schema::{order_lines, order_status_history, sale_lines};
+use crate::models::tools::{NewOrderLine, NewOrderStatusChange, OrderField, OrderInSQL, OrderLine, OrderStatusChange, OrderWithLines, OrdersRelatedResponse, OrdersRequest, ReceivedLine, UnstockedLine, StatusChange, Status, SaleRequest, SaleInSQL, SaleRelatedResponse, SaleField, NewSaleLine, SaleLine, SaleWithLines, round_cents};
 use crate::handlers::employee_handler::connect_db;
 use diesel::prelude::*;
 use diesel::QueryDsl;
@@ -23,6 +24,11 @@ use crate::employee_schema::employees::dsl::employees;
 use crate::employee_schema::employees::{employee_id, permission};
 use futures::stream::TryStreamExt;
 use crate::redis::redis_connection::send_data_to_ai;
+use crate::auth::extractor::AuthContext;
+use crate::audit::audit_log::{self as audit, Actor, AuditEntry};
+use crate::metrics::registry::{ORDERS_CREATED, ORDERS_DELIVERED, SALES_RECORDED};
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct InventoryData{
@@ -47,350 +53,336 @@ pub fn generate_sku(prefix: Option<&str>) -> String {
         None => format!("{}-{}", timestamp, random_string),
     }
 }
-pub async fn handle_request(req: HttpRequest) -> Result<Database, HttpResponse> {
-    match get_database_inventory(req).await {
-        Ok(db) => Ok(db),
-        Err(err) => Err(HttpResponse::InternalServerError().json(serde_json::json!({"error": err.to_string()}))),
-    }
+pub async fn handle_request(auth: &AuthContext) -> Result<Database, AppError> {
+    get_database_inventory(auth).await.map_err(|err| AppError::Internal(format!("inventory database unavailable: {}", err)))
 }
-pub async fn get_item_quantity(collection: &Collection<InventoryItem>, product_name: &str, ) -> Result<Option<i32>, actix_web::Error> {
-    let filter = doc! {"item_name": product_name};
-
-    match collection.find_one(filter, None).await {
-        Ok(Some(item)) => Ok(Some(item.quantity)),
-        Ok(None) => Ok(None),
-        Err(e) => {
-            Err(actix_web::error::ErrorInternalServerError(format!(
-                "Database error: {}", e
-            )))
+/// Add received stock to the inventory, creating the item on first delivery.
+async fn stock_received(collection: &Collection<InventoryItem>, line: &OrderLine, quantity: i32) -> mongodb::error::Result<()> {
+    let product_name = &line.product_name;
+    match collection.find_one(doc! {"item_name": product_name}, None).await? {
+        Some(item) => {
+            let new_quantity = item.quantity + quantity;
+            let current_price = item.price as f64;
+            let profit_percent = rand::thread_rng().gen_range(3.0..10.0);
+            let new_price = current_price * (1.0 + profit_percent / 100.0);
+
+            collection
+                .update_one(
+                    doc! { "item_name": product_name },
+                    doc! {
+                        "$set": {
+                            "quantity": new_quantity,
+                            "price": new_price
+                        }
+                    },
+                    None,
+                )
+                .await?;
+        }
+        None => {
+            let new_item = InventoryItem {
+                item_name: product_name.to_string(),
+                SKU: line.sku.clone().unwrap_or_else(|| generate_sku(Some(product_name))),
+                quantity,
+                price: line.unit_cost as f32,
+                category: line.category.clone(),
+            };
+            collection.insert_one(new_item, None).await?;
         }
     }
+    Ok(())
 }
-pub async fn set_orders(user_request : web::Json<OrdersRequest>, req : HttpRequest) -> HttpResponse {
 
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => {
-            conn
-        },
-        Err(err) => {
-            return err
-        }
-    };
+/// Take sold stock out of the inventory in a single update, so concurrent sales
+/// cannot overwrite each other's counts. `false` if the item is missing or has
+/// fewer units left than were sold; nothing is taken then.
+async fn stock_sold(collection: &Collection<InventoryItem>, line: &SaleLine) -> mongodb::error::Result<bool> {
+    let result = collection
+        .update_one(
+            doc! { "item_name": &line.product_name, "quantity": { "$gte": line.quantity } },
+            doc! { "$inc": { "quantity": -line.quantity } },
+            None,
+        )
+        .await?;
+    Ok(result.matched_count == 1)
+}
 
-    let mut product_ids  = Vec::new();
-    let mut quantities  = Vec::new();
+pub async fn set_orders(user_request : Validated<OrdersRequest>, auth: AuthContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
 
-    for (product, quantity) in &user_request.products {
-        product_ids.push(product.clone());
-        quantities.push(*quantity);
-    }
+    let mut conn = connect_db(&auth).await?;
 
     let new_order = OrderInSQL{
         order_id:None,
         supplier_name : user_request.supplier_name.clone(),
-        product_id: product_ids.clone(),
-        quantity_ordered: quantities.clone(),
-        categories : user_request.categories.clone(),
-        price : user_request.price.clone(),
         order_date : Some(Utc::now().naive_utc()),
-        status : Status::Pending.as_str().to_string(),
+        status : Status::Pending,
     };
-
-    let insert_result = web::block(move || {
-        diesel::insert_into(Orders)
-            .values(&new_order)
-            .execute(&mut conn)
-    }).await;
-
-    match insert_result {
-        Ok(Ok(_)) => HttpResponse::Created().json(OrdersRelatedResponse {
-            message: "Order stablest!!".to_string(),
-        }),
-        Ok(Err(e)) => {
-            error!("server Error: {:?}", e);
-            HttpResponse::InternalServerError().json("Failed to Order")
-        }
-        Err(e) => {
-            error!("Thread pool error during database insert: {:?}", e);
-            HttpResponse::InternalServerError().json("Database insert failed")
-        }
-    }
-
+    let Validated(request) = user_request;
+
+    let actor = Actor::new(&auth, &req);
+    let order = web::block(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let order = diesel::insert_into(Orders)
+                .values(&new_order)
+                .get_result::<OrderField>(conn)?;
+            let new_lines: Vec<NewOrderLine> = request.lines.into_iter().map(|line| NewOrderLine {
+                order_id: order.order_id,
+                sku: line.sku,
+                product_name: line.product_name,
+                category: line.category,
+                quantity: line.quantity,
+                unit_cost: line.unit_cost,
+            }).collect();
+            let lines = diesel::insert_into(order_lines::table)
+                .values(&new_lines)
+                .get_results::<OrderLine>(conn)?;
+            let placed = diesel::insert_into(order_status_history::table)
+                .values(&NewOrderStatusChange {
+                    order_id: order.order_id,
+                    from_status: None,
+                    to_status: order.status,
+                    changed_by: Some(actor.id),
+                    actor_kind: Some(actor.kind.to_string()),
+                    changed_at: order.order_date.unwrap_or_else(|| Utc::now().naive_utc()),
+                })
+                .get_result::<OrderStatusChange>(conn)?;
+            let order = OrderWithLines { order, lines, history: vec![placed] };
+            audit::record(conn, &actor, AuditEntry::new("order.create", "order", order.order.order_id)
+                .after(json!(order)))?;
+            Ok(order)
+        })
+    }).await??;
+
+    ORDERS_CREATED.inc();
+    Ok(HttpResponse::Created().json(OrdersRelatedResponse {
+        message: "Order stablest!!".to_string(),
+        order,
+        unstocked: Vec::new(),
+    }))
 }
 
-pub async fn display_orders(req: HttpRequest) -> HttpResponse {
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
-    };
-
-    match Orders.load::<OrderField>(&mut conn) {
-        Ok(order_list) => {
+pub async fn display_orders(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+    let order_list = Orders.order(order_id.asc()).load::<OrderField>(&mut conn)?;
+    let order_list = OrderWithLines::attach(&mut conn, order_list)?;
+    Ok(HttpResponse::Ok().json(json!({ "orders": order_list })))
+}
 
-            match serde_json::to_string(&order_list) {
-                Ok(_) => HttpResponse::Ok().json(json!({ "orders": order_list })),
-                Err(e) => {
-                    println!("❌ JSON serialization failed: {:?}", e);
-                    HttpResponse::InternalServerError().json(json!({ "error": format!("Serialization error: {}", e) }))
+/// Quantities arriving with a move to `next`, as (line id, quantity). A delivery
+/// receives everything still outstanding, a partial delivery what was listed.
+fn arrivals(lines: &[OrderLine], next: Status, received: &[ReceivedLine]) -> Result<Vec<(i32, i32)>, AppError> {
+    match next {
+        Status::Delivered => Ok(lines
+            .iter()
+            .map(|line| (line.line_id, line.quantity - line.quantity_received))
+            .filter(|(_, outstanding)| *outstanding > 0)
+            .collect()),
+        Status::PartiallyDelivered => {
+            let mut arrived: Vec<(i32, i32)> = Vec::new();
+            for receipt in received {
+                let line = lines
+                    .iter()
+                    .find(|line| line.line_id == receipt.line_id)
+                    .ok_or_else(|| AppError::BadRequest(format!("Line {} is not on this order", receipt.line_id)))?;
+                let listed: i32 = arrived.iter().filter(|(id, _)| *id == line.line_id).map(|(_, quantity)| quantity).sum();
+                let outstanding = line.quantity - line.quantity_received - listed;
+                if receipt.quantity > outstanding {
+                    return Err(AppError::Conflict(format!("Line {} has only {} outstanding", line.line_id, outstanding)));
                 }
+                arrived.push((line.line_id, receipt.quantity));
             }
+            Ok(arrived)
         }
-        Err(e) => {
-            println!("❌ Diesel query failed: {:?}", e);
-            HttpResponse::InternalServerError().json(json!({ "error": "Error retrieving orders" }))
-        }
+        _ => Ok(Vec::new()),
     }
 }
 
-pub async fn status_change(user_request: web::Json<StatusChange>, req: HttpRequest) -> HttpResponse {
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
-    };
-
-    if user_request.status == "delivered" {
-        let result = web::block({
-            move || {
-                Orders
-                    .filter(order_id.eq(user_request.id))
-                    .first::<OrderField>(&mut conn)
-                    .optional()
-                    .map_err(|e| e.to_string())
+pub async fn status_change(user_request: Validated<StatusChange>, auth: AuthContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+
+    let actor = Actor::new(&auth, &req);
+    let Validated(change) = user_request;
+    let next: Status = change.status.parse().map_err(AppError::BadRequest)?;
+
+    // Lock the order, check the transition, book what arrived and record the new
+    // status and who set it in one transaction. Stock goes into the inventory only
+    // once this has committed, so a repeated request cannot receive goods twice.
+    let (order, arrived) = web::block(move || {
+        conn.transaction::<_, AppError, _>(|conn| {
+            let order = Orders
+                .filter(order_id.eq(change.id))
+                .for_update()
+                .first::<OrderField>(conn)
+                .optional()?
+                .ok_or_else(|| AppError::not_found("Order"))?;
+            let previous = order.status;
+            if !previous.can_become(next) {
+                return Err(AppError::InvalidTransition { from: previous, to: next });
             }
-        }).await;
-        let db_holder = match handle_request(req).await {
-            Ok(db) => db,
-            Err(err) => return err,
-        };
-        let collection: Collection<InventoryItem> = db_holder.collection("inventory");
-
-        return match result {
-            Ok(inner_result) => match inner_result {
-                Ok(Some(order)) => {
-                    for (product_name, product_quantity, product_price, product_category) in order
-                        .product_id
-                        .iter()
-                        .zip(order.quantity_ordered.iter())
-                        .zip(order.price.iter())
-                        .zip(order.categories.iter())
-                        .map(|(((a, b), c), d)| (a, b, c, d))
-                    {
-                        match get_item_quantity(&collection, product_name).await {
-                            Ok(Some(existing_quantity)) => {
-                                let new_quantity = existing_quantity + product_quantity;
-
-                                let existing_doc = collection
-                                    .find_one(doc! {"item_name": product_name}, None)
-                                    .await
-                                    .unwrap_or(None);
-
-                                if let Some(item) = existing_doc {
-                                    let current_price = item.price as f64;
-                                    let profit_percent = rand::thread_rng().gen_range(3.0..10.0);
-                                    let new_price = current_price * (1.0 + profit_percent / 100.0);
-
-                                    if let Err(e) = collection
-                                        .update_one(
-                                            doc! { "item_name": product_name },
-                                            doc! {
-                                                        "$set": {
-                                                            "quantity": new_quantity,
-                                                            "price": new_price
-                                                        }
-                                                    },
-                                            None,
-                                        )
-                                        .await
-                                    {
-                                        return HttpResponse::InternalServerError()
-                                            .json(json!({ "error": e.to_string() }));
-                                    }
-                                }
-                            }
-
-                            Ok(None) => {
-                                let new_item = InventoryItem {
-                                    item_name: product_name.to_string(),
-                                    SKU: generate_sku(Some(product_name)),
-                                    quantity: *product_quantity,
-                                    price: *product_price,
-                                    category: product_category.to_string(),
-                                };
-
-                                if let Err(e) = collection.insert_one(new_item, None).await {
-                                    return HttpResponse::InternalServerError()
-                                        .json(json!({ "error": e.to_string() }));
-                                }
-                            }
-
-                            Err(e) => {
-                                return HttpResponse::InternalServerError()
-                                    .json(json!({ "error": e.to_string() }));
-                            }
-                        }
-                    }
-
-                    HttpResponse::Ok().json("Order status updated and inventory adjusted.")
-                }
-
-                Ok(None) => {
-                    HttpResponse::NotFound().json("Order not found")
-                }
 
-                Err(err_msg) => {
-                    HttpResponse::InternalServerError().json(err_msg)
-                }
-            },
+            let lines = order_lines::table
+                .filter(order_lines::order_id.eq(order.order_id))
+                .load::<OrderLine>(conn)?;
+            let arrived = arrivals(&lines, next, &change.received)?;
+            for (line_ref, quantity) in &arrived {
+                diesel::update(order_lines::table.find(line_ref))
+                    .set(order_lines::quantity_received.eq(order_lines::quantity_received + quantity))
+                    .execute(conn)?;
+            }
 
-            Err(blocking_err) => {
-                HttpResponse::InternalServerError()
-                    .json(format!("Blocking error: {}", blocking_err))
+            diesel::update(Orders.filter(order_id.eq(order.order_id)))
+                .set(status.eq(next))
+                .execute(conn)?;
+            diesel::insert_into(order_status_history::table)
+                .values(&NewOrderStatusChange {
+                    order_id: order.order_id,
+                    from_status: Some(previous),
+                    to_status: next,
+                    changed_by: Some(actor.id),
+                    actor_kind: Some(actor.kind.to_string()),
+                    changed_at: Utc::now().naive_utc(),
+                })
+                .execute(conn)?;
+            audit::record(conn, &actor, AuditEntry::new("order.status_change", "order", order.order_id)
+                .before(json!({ "status": previous }))
+                .after(json!({ "status": next, "received": arrived })))?;
+
+            let order = OrderWithLines::attach(conn, vec![OrderField { status: next, ..order }])?
+                .pop()
+                .ok_or_else(|| AppError::not_found("Order"))?;
+            Ok((order, arrived))
+        })
+    }).await??;
+
+    // The receipt is committed whatever happens below, so a failure here is
+    // reported with the lines concerned rather than as an error the client
+    // would retry: a retry cannot add the stock again
+    let mut unstocked = Vec::new();
+    if !arrived.is_empty() {
+        let inventory = handle_request(&auth)
+            .await
+            .map(|db_holder| db_holder.collection::<InventoryItem>("inventory"));
+        for (line_ref, quantity) in arrived {
+            let Some(line) = order.lines.iter().find(|line| line.line_id == line_ref) else { continue };
+            let result = match &inventory {
+                Ok(collection) => stock_received(collection, line, quantity).await.map_err(AppError::from),
+                Err(err) => Err(AppError::Internal(err.to_string())),
+            };
+            if let Err(err) = result {
+                error!(order_id = order.order.order_id, line_id = line_ref, quantity, "received stock not added to inventory: {}", err);
+                unstocked.push(UnstockedLine { line_id: line_ref, product_name: line.product_name.clone(), quantity });
             }
         }
     }
 
-    // Update order status if not "Delivered"
-    let update_result = web::block({
-        move || {
-            diesel::update(Orders.filter(order_id.eq(user_request.id)))
-                .set(status.eq(user_request.status.clone()))
-                .execute(&mut conn)
-        }
-    }).await;
-
-    match update_result {
-        Ok(rows_affected) => {
-            if rows_affected == Ok(0) {
-                HttpResponse::NotFound().json("Order not found")
-            } else {
-                HttpResponse::Ok().json("Order status updated successfully")
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to update order status"),
+    if next == Status::Delivered {
+        ORDERS_DELIVERED.inc();
     }
+    let message = match unstocked.len() {
+        0 => format!("Order is now {}", next),
+        lines => format!("Order is now {}, but the inventory did not take the stock of {} line(s); add it by hand", next, lines),
+    };
+    Ok(HttpResponse::Ok().json(OrdersRelatedResponse { message, order, unstocked }))
 }
 
-pub async fn set_sales(user_request: web::Json<SaleRequest>, req: HttpRequest) -> HttpResponse {
+pub async fn set_sales(user_request: Validated<SaleRequest>, auth: AuthContext, req: HttpRequest) -> Result<HttpResponse, AppError> {
     // Connect to PostgreSQL
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
-    };
-
-    // Collect product IDs and quantities
-    let mut product_ids = Vec::new();
-    let mut quantities = Vec::new();
+    let mut conn = connect_db(&auth).await?;
+    let Validated(request) = user_request;
 
-    for (product, quantity) in &user_request.products {
-        product_ids.push(product.clone());
-        quantities.push(*quantity);
-    }
-
-    // Prepare the SaleInSQL struct
+    // Line totals, the sale total and the seller are always decided here
+    let line_totals: Vec<f64> = request.lines.iter().map(|line| line.line_total()).collect();
     let new_sale = SaleInSQL {
         sale_id: None,
-        product_id: product_ids.clone(),
-        quantity_sold: quantities.clone(),
-        price: user_request.price.clone(),
-        total_price: user_request.price.iter().sum::<f64>(),
-        sold_by: user_request.sale_by.clone(),
+        total_price: round_cents(line_totals.iter().sum::<f64>()),
+        sold_by: auth.principal.id(),
         sale_date: Some(Utc::now().naive_utc()),
-        categories: user_request.categories.clone(),
+        sold_by_kind: Some(auth.principal.kind().to_string()),
     };
+    let new_lines: Vec<NewSaleLine> = request.lines.iter().zip(line_totals).map(|(line, line_total)| NewSaleLine {
+        sale_id: 0,
+        product_name: line.product_name.clone(),
+        category: line.category.clone(),
+        quantity: line.quantity,
+        unit_price: line.unit_price,
+        discount: line.discount,
+        line_total,
+    }).collect();
 
     // Insert sale into PostgreSQL
-    let insert_result = web::block(move || {
-        diesel::insert_into(sales)
-            .values(&new_sale)
-            .execute(&mut conn)
-    }).await;
-
-    // Now handle MongoDB inventory logic
-    let db_holder = match handle_request(req.clone()).await {
-        Ok(db) => db,
-        Err(_) => return HttpResponse::InternalServerError().body("Inventory DB connection failed"),
-    };
-    let collection: Collection<InventoryItem> = db_holder.collection("inventory");
-
-    for ((product_name, sold_quantity), category) in product_ids.iter().zip(quantities.iter()).zip(user_request.categories.iter()) {
-        let filter = doc! { "item_name": product_name };
-        match collection.find_one(filter.clone(), None).await {
-            Ok(Some(existing_item)) => {
-                // Calculate new quantity
-                let new_quantity = (existing_item.quantity - sold_quantity).max(0);
-
-                // Update MongoDB document
-                if let Err(e) = collection
-                    .update_one(filter, doc! { "$set": { "quantity": new_quantity } }, None)
-                    .await
-                {
-                    error!("MongoDB update error: {}", e);
-                }
-            }
-            Ok(None) => {
-                error!("Product `{}` not found in inventory", product_name);
-                // You can also return 404 or just continue like here
+    let actor = Actor::new(&auth, &req);
+    let sale = web::block(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let sale = diesel::insert_into(sales)
+                .values(&new_sale)
+                .get_result::<SaleField>(conn)?;
+            let new_lines: Vec<NewSaleLine> = new_lines.into_iter().map(|line| NewSaleLine { sale_id: sale.sale_id, ..line }).collect();
+            let lines = diesel::insert_into(sale_lines::table)
+                .values(&new_lines)
+                .get_results::<SaleLine>(conn)?;
+            let sale = SaleWithLines { sale, lines };
+            audit::record(conn, &actor, AuditEntry::new("sale.create", "sale", sale.sale.sale_id)
+                .after(json!(sale)))?;
+            Ok(sale)
+        })
+    }).await??;
+
+    // Inventory and the AI service only hear about sales that were stored. The
+    // sale is committed whatever happens below, so a failure here is reported
+    // with the lines concerned rather than as an error the client would retry:
+    // a retry would record the sale twice
+    let inventory = handle_request(&auth)
+        .await
+        .map(|db_holder| db_holder.collection::<InventoryItem>("inventory"));
+    let mut unstocked = Vec::new();
+    for line in &sale.lines {
+        let result = match &inventory {
+            Ok(collection) => stock_sold(collection, line).await.map_err(AppError::from),
+            Err(err) => Err(AppError::Internal(err.to_string())),
+        };
+        let taken = match result {
+            Ok(true) => true,
+            Ok(false) => {
+                warn!(sale_id = sale.sale.sale_id, line_id = line.line_id, "`{}` is not in the inventory or has fewer than {} left", line.product_name, line.quantity);
+                false
             }
-            Err(e) => {
-                error!("MongoDB read error: {}", e);
-                return HttpResponse::InternalServerError()
-                    .json(serde_json::json!({ "error": "MongoDB inventory fetch error" }));
+            Err(err) => {
+                error!(sale_id = sale.sale.sale_id, line_id = line.line_id, "sold stock not taken from inventory: {}", err);
+                false
             }
+        };
+        if !taken {
+            unstocked.push(UnstockedLine { line_id: line.line_id, product_name: line.product_name.clone(), quantity: line.quantity });
         }
 
         // Optional: notify AI after inventory change
-        if let Err(err) = send_data_to_ai(category.parse().unwrap_or_default(), *sold_quantity, req.clone()).await {
-            println!("AI sync failed: {}", err);
+        if let Err(err) = send_data_to_ai(line.category.parse().unwrap_or_default(), line.quantity, &auth.tenant).await {
+            warn!("AI sync failed: {}", err);
         }
     }
 
     // Final response
-    match insert_result {
-        Ok(Ok(_)) => HttpResponse::Created().json(SaleRelatedResponse {
-            message: "Order established and inventory updated!".to_string(),
-        }),
-        Ok(Err(e)) => {
-            error!("PostgreSQL insert error: {:?}", e);
-            HttpResponse::InternalServerError().json("Failed to record sale")
-        }
-        Err(e) => {
-            error!("Thread pool error during database insert: {:?}", e);
-            HttpResponse::InternalServerError().json("Database insert failed")
-        }
-    }
+    SALES_RECORDED.inc();
+    let message = match unstocked.len() {
+        0 => "Order established and inventory updated!".to_string(),
+        lines => format!("Sale recorded, but the inventory did not give up the stock of {} line(s); take it out by hand", lines),
+    };
+    Ok(HttpResponse::Created().json(SaleRelatedResponse { message, sale, unstocked }))
 }
 
 
-pub async fn show_all_sales(req : HttpRequest) -> HttpResponse {
-    let mut conn = match connect_db(&req).await {
-        Ok(conn) => conn,
-        Err(err) => return err,
-    };
-
-    match sales.load::<SaleField>(&mut conn) {
-        Ok(Sale_list) => HttpResponse::Ok().json(json!({"orders": Sale_list})),
-        Err(_) => HttpResponse::InternalServerError().json("Error retrieving orders"),
-    }
+pub async fn show_all_sales(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let mut conn = connect_db(&auth).await?;
+    let sale_list = sales.load::<SaleField>(&mut conn)?;
+    let sale_list = SaleWithLines::attach(&mut conn, sale_list)?;
+    Ok(HttpResponse::Ok().json(json!({"orders": sale_list})))
 }
 
-pub async fn get_inventory(req: HttpRequest) -> HttpResponse {
-    let db_holder = match handle_request(req).await {
-        Ok(db) => db,
-        Err(_) => return HttpResponse::InternalServerError().body("Database connection failed"),
-    };
-
+pub async fn get_inventory(auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let db_holder = handle_request(&auth).await?;
     let collection: Collection<InventoryData> = db_holder.collection("inventory");
 
-    let cursor = match collection.find(None, None).await {
-        Ok(cursor) => cursor,
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to fetch inventory"),
-    };
-
-    let inventory: Result<Vec<InventoryData>, mongodb::error::Error> = cursor.try_collect().await;
-
-    match inventory {
-        Ok(items) => HttpResponse::Ok().json(items),
-        Err(_) => HttpResponse::InternalServerError().body("Error processing inventory data"),
-    }
+    let cursor = collection.find(None, None).await?;
+    let items: Vec<InventoryData> = cursor.try_collect().await?;
+    Ok(HttpResponse::Ok().json(items))
 }
-
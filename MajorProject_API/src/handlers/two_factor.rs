@@ -0,0 +1,248 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Text};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::auth::extractor::{AuthContext, Principal};
+use crate::auth::session::{self, PrincipalKind, SessionClaims};
+use crate::auth::{session_store, throttle, token, totp};
+use crate::config::settings::Settings;
+use crate::connect_sql::sql_handler::DbPool;
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
+use crate::models::user_requests::{LogInUser, TotpCode, TwoFactorLogIn};
+use crate::redis::redis_connection::get_redis_connection;
+use crate::schema::users;
+
+/// Marks a signed token as a 2FA login challenge rather than a session.
+const CHALLENGE_PURPOSE: &str = "admin_2fa";
+/// Time allowed between the password step and the code step.
+const CHALLENGE_TTL_SECS: i64 = 300;
+
+#[derive(Serialize, Deserialize)]
+struct TwoFactorChallenge {
+    purpose: String,
+    /// Key of the Redis record that makes the challenge single-use.
+    id: String,
+    user_id: i32,
+    exp: i64,
+}
+
+/// Which second factor was accepted.
+enum SecondFactor {
+    Totp { step: i64 },
+    Recovery { code_hash: String },
+}
+
+define_sql_function!(fn array_remove(array: Array<Text>, element: Text) -> Array<Text>);
+
+fn challenge_key(id: &str) -> String {
+    format!("2fa_challenge:{}", id)
+}
+
+fn admin_id(auth: &AuthContext) -> Result<i32, AppError> {
+    match auth.principal {
+        Principal::Admin { user_id } => Ok(user_id),
+        Principal::Employee { .. } | Principal::ApiKey { .. } => Err(AppError::Forbidden(
+            "Two-factor authentication is only available to company admins".to_string(),
+        )),
+    }
+}
+
+fn load_admin(conn: &mut PgConnection, id: i32) -> Result<LogInUser, AppError> {
+    users::table
+        .find(id)
+        .filter(users::deleted_at.is_null())
+        .first::<LogInUser>(conn)
+        .optional()?
+        .ok_or_else(|| AppError::not_found("User"))
+}
+
+/// Accept either a current TOTP code or an unused recovery code.
+fn check_second_factor(admin: &LogInUser, code: &str) -> Option<SecondFactor> {
+    let secret = admin.totp_secret.as_deref()?;
+    if let Some(step) = totp::verify(secret, code, Utc::now().timestamp(), admin.totp_last_used_step) {
+        return Some(SecondFactor::Totp { step });
+    }
+    let code_hash = token::hash(code.trim());
+    admin
+        .totp_recovery_codes
+        .contains(&code_hash)
+        .then_some(SecondFactor::Recovery { code_hash })
+}
+
+/// Record that a second factor was used so it cannot be used again.
+///
+/// Each update only applies while the factor is still unused, so of two
+/// concurrent logins with the same code only one gets `true`.
+fn consume_second_factor(conn: &mut PgConnection, admin: &LogInUser, factor: SecondFactor) -> QueryResult<bool> {
+    let rows = match factor {
+        SecondFactor::Totp { step } => diesel::update(
+            users::table
+                .find(admin.user_id)
+                .filter(users::totp_last_used_step.is_null().or(users::totp_last_used_step.lt(step))),
+        )
+        .set(users::totp_last_used_step.eq(step))
+        .execute(conn)?,
+        SecondFactor::Recovery { code_hash } => diesel::update(
+            users::table
+                .find(admin.user_id)
+                .filter(users::totp_recovery_codes.contains(vec![code_hash.clone()])),
+        )
+        .set(users::totp_recovery_codes.eq(array_remove(users::totp_recovery_codes, code_hash)))
+        .execute(conn)?,
+    };
+    Ok(rows == 1)
+}
+
+/// Response for a correct password on an account with 2FA: a short-lived challenge
+/// to exchange, together with a code, at `/api/login/2fa`.
+///
+/// Each challenge allows one code attempt; it is remembered in Redis until then,
+/// and without Redis no challenge is issued.
+pub async fn challenge_response(admin: &LogInUser) -> Result<HttpResponse, AppError> {
+    let challenge = TwoFactorChallenge {
+        purpose: CHALLENGE_PURPOSE.to_string(),
+        id: token::generate(),
+        user_id: admin.user_id,
+        exp: Utc::now().timestamp() + CHALLENGE_TTL_SECS,
+    };
+    let mut con = get_redis_connection().await?;
+    con.set_ex::<_, _, ()>(challenge_key(&challenge.id), admin.user_id, CHALLENGE_TTL_SECS as u64).await?;
+    let token = session::sign(&challenge)
+        .map_err(|e| AppError::Internal(format!("failed to sign 2FA challenge: {}", e)))?;
+    Ok(HttpResponse::Ok().json(json!({ "two_factor_required": true, "challenge": token })))
+}
+
+/// Second login step for admins with 2FA enabled.
+pub async fn login_two_factor(pool: web::Data<DbPool>, user_request: Validated<TwoFactorLogIn>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let challenge = match session::verify_signed::<TwoFactorChallenge>(&user_request.challenge) {
+        Ok(c) if c.purpose == CHALLENGE_PURPOSE && c.exp > Utc::now().timestamp() => c,
+        _ => return Err(AppError::Unauthorized("Invalid or expired login challenge".to_string())),
+    };
+
+    let mut conn = pool.get()?;
+    let admin = load_admin(&mut conn, challenge.user_id)?;
+
+    let account = throttle::account_key("admin", &admin.email);
+    let ip = throttle::client_ip(&req);
+    throttle::check(&account, &ip).await.map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
+
+    // Used up by this attempt whatever its outcome; another code needs the password again
+    let mut con = get_redis_connection().await?;
+    if con.del::<_, usize>(challenge_key(&challenge.id)).await? == 0 {
+        return Err(AppError::Unauthorized("Invalid or expired login challenge".to_string()));
+    }
+
+    let consumed = match check_second_factor(&admin, &user_request.code) {
+        Some(factor) if admin.totp_enabled => consume_second_factor(&mut conn, &admin, factor)?,
+        _ => false,
+    };
+    if !consumed {
+        let delay = throttle::record_failure(&account, &ip).await;
+        tokio::time::sleep(delay).await;
+        return Err(AppError::Unauthorized("Invalid two-factor code".to_string()));
+    }
+    throttle::record_success(&account).await;
+
+    let claims = SessionClaims::new(admin.database_name.clone(), admin.user_id, PrincipalKind::Admin, admin.role.clone());
+    let session_cookie = session_store::start(&claims, &req).await?;
+    Ok(HttpResponse::Ok().cookie(session_cookie).json(json!({
+        "id": admin.user_id,
+        "name": admin.name,
+        "email": admin.email,
+        "company_name": admin.company_name,
+        "role": admin.role
+    })))
+}
+
+/// Start enrollment: generate a secret that becomes active once confirmed.
+pub async fn enroll_totp(pool: web::Data<DbPool>, settings: web::Data<Settings>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let id = admin_id(&auth)?;
+    let mut conn = pool.get()?;
+    let admin = load_admin(&mut conn, id)?;
+    if admin.totp_enabled {
+        return Err(AppError::Conflict("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    diesel::update(users::table.find(id))
+        .set((
+            users::totp_secret.eq(Some(&secret)),
+            users::totp_last_used_step.eq(None::<i64>),
+            users::totp_recovery_codes.eq(Vec::<String>::new()),
+        ))
+        .execute(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "secret": secret,
+        "otpauth_uri": totp::otpauth_uri(&settings.auth.totp_issuer, &admin.email, &secret),
+    })))
+}
+
+/// Finish enrollment with a first valid code; returns the recovery codes once.
+pub async fn activate_totp(pool: web::Data<DbPool>, auth: AuthContext, user_request: Validated<TotpCode>) -> Result<HttpResponse, AppError> {
+    let id = admin_id(&auth)?;
+    let mut conn = pool.get()?;
+    let admin = load_admin(&mut conn, id)?;
+    if admin.totp_enabled {
+        return Err(AppError::Conflict("Two-factor authentication is already enabled".to_string()));
+    }
+    let Some(secret) = admin.totp_secret.as_deref() else {
+        return Err(AppError::BadRequest("Start enrollment first".to_string()));
+    };
+    let Some(step) = totp::verify(secret, &user_request.code, Utc::now().timestamp(), None) else {
+        return Err(AppError::BadRequest("Invalid two-factor code".to_string()));
+    };
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let hashed: Vec<String> = recovery_codes.iter().map(|c| token::hash(c)).collect();
+    diesel::update(users::table.find(id))
+        .set((
+            users::totp_enabled.eq(true),
+            users::totp_last_used_step.eq(Some(step)),
+            users::totp_recovery_codes.eq(hashed),
+        ))
+        .execute(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(json!({ "recovery_codes": recovery_codes })))
+}
+
+/// Turn 2FA off; requires a current code or a recovery code, which is used up.
+///
+/// Wrong codes count against the same lockout as failed logins.
+pub async fn disable_totp(pool: web::Data<DbPool>, auth: AuthContext, user_request: Validated<TotpCode>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let id = admin_id(&auth)?;
+    let mut conn = pool.get()?;
+    let admin = load_admin(&mut conn, id)?;
+    if !admin.totp_enabled {
+        return Err(AppError::Conflict("Two-factor authentication is not enabled".to_string()));
+    }
+
+    let account = throttle::account_key("admin", &admin.email);
+    let ip = throttle::client_ip(&req);
+    throttle::check(&account, &ip).await.map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
+
+    let consumed = match check_second_factor(&admin, &user_request.code) {
+        Some(factor) => consume_second_factor(&mut conn, &admin, factor)?,
+        None => false,
+    };
+    if !consumed {
+        let delay = throttle::record_failure(&account, &ip).await;
+        tokio::time::sleep(delay).await;
+        return Err(AppError::BadRequest("Invalid two-factor code".to_string()));
+    }
+
+    diesel::update(users::table.find(id))
+        .set((
+            users::totp_secret.eq(None::<String>),
+            users::totp_enabled.eq(false),
+            users::totp_last_used_step.eq(None::<i64>),
+            users::totp_recovery_codes.eq(Vec::<String>::new()),
+        ))
+        .execute(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json("Two-factor authentication disabled"))
+}
@@ -1,42 +1,31 @@
-use std::env;
-use actix_web::{web, HttpResponse, Responder,cookie};
-use actix_web::cookie::SameSite;
-use bcrypt::{hash, verify, DEFAULT_COST};
-use crate::models::user_requests::{CreateUserRequest, CreateUserResponse, CreateUserLogInRequest, CreateUserLogInResponse};
-use crate::connect_sql::sql_handler::{DbError, DbPool};
+use std::path::Path;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::http::header;
+use bcrypt::{hash, DEFAULT_COST};
+use crate::models::user_requests::{CloseAccountRequest, CreateUserRequest, CreateUserLogInRequest};
+use crate::connect_sql::sql_handler::DbPool;
 use crate::models::user_requests::{User,LogInUser};
 use crate::schema::users as adminD;
 use diesel::prelude::*;
 use chrono::Utc;
-use diesel::sql_query;
 use crate::schema::users::dsl::*;
-use dotenv::dotenv;
-use diesel::{PgConnection, RunQueryDsl};
-use diesel::dsl::insert_into;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use crate::connect_sql::sql_handler::LogInUser as login ;
-use crate::connect_sql::no_sql::get_database;
-
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("Employee");
-
-pub async fn migration(mut db_connect: PgConnection) -> HttpResponse {
-    match db_connect.run_pending_migrations(MIGRATIONS) {
-        Ok(_) => HttpResponse::Created().json("User and database created successfully"),
-        Err(e) => {
-            eprintln!("Failed to apply migrations: {:?}", e);
-            HttpResponse::InternalServerError().json("Failed to apply migrations")
-        }
-    }
-}
-
-
-pub async fn create_user(pool: web::Data<DbPool>, user_request: web::Json<CreateUserRequest>) -> impl Responder {
+use diesel::RunQueryDsl;
+use crate::auth::extractor::{AuthContext, Principal};
+use crate::auth::session::{self, PrincipalKind, SessionClaims};
+use crate::auth::session_store;
+use crate::auth::password::verify_or_dummy;
+use crate::auth::throttle;
+use crate::config::settings::Settings;
+use crate::error::app_error::AppError;
+use crate::validation::extractor::Validated;
+use crate::handlers::two_factor;
+use crate::tenant::deprovision;
+use crate::tenant::naming;
+use crate::tenant::provision::{self, ProvisionError};
+
+pub async fn create_user(pool: web::Data<DbPool>, settings: web::Data<Settings>, user_request: Validated<CreateUserRequest>, req: HttpRequest) -> Result<HttpResponse, AppError> {
     // Hash the password
-
-    let hashed_password = match hash(&user_request.password, DEFAULT_COST) {
-        Ok(hash) => hash,
-        Err(_) => return HttpResponse::InternalServerError().json("Password hashing failed"),
-    };
+    let hashed_password = hash(&user_request.password, DEFAULT_COST)?;
 
     // Create a new user instance
     let new_user = User {
@@ -46,130 +35,105 @@ pub async fn create_user(pool: web::Data<DbPool>, user_request: web::Json<Create
         company_name: user_request.company_name.clone(),
         role: user_request.role.clone(),
         password: hashed_password,
-        database_name: format!("{}_{}_{}", user_request.name.to_lowercase(), user_request.company_name.to_lowercase(), Utc::now().format("%Y%m%d%H%M%S")).replace(" ",""),
+        database_name: naming::database_name(&user_request.company_name),
         created_at: Some(Utc::now().naive_utc()), // Convert to NaiveDateTime
+        tenancy: settings.tenancy.mode.as_str().to_string(),
     };
-
-    // Get a mutable connection from the pool
-    let mut conn = match pool.get() {
-        Ok(conn) => conn,
-        Err(_) => return HttpResponse::InternalServerError().json("Failed to get a database connection"),
-    };
-
-    // Insert the new user into the database
-    match insert_into(adminD::table)
-        .values(&new_user)
-        .execute(&mut conn)
-    {
-        Ok(_) => HttpResponse::Created().json(CreateUserResponse {
-            message: "User created successfully".to_string(),
-        }),
-        Err(e) => {
-            eprintln!("Failed to insert user: {:?}", e);
-            return HttpResponse::InternalServerError().json("Failed to create user");
-        }
-    };
+    let tenant = new_user.database_name.clone();
+
+    // Admin row, Postgres database, migrations and Mongo database, rolled back together on failure
+    let new_user_id = provision::provision(&pool, new_user).await.map_err(|e| match e {
+        ProvisionError::InvalidName(_) => AppError::BadRequest("Company name cannot be used".to_string()),
+        e => AppError::Internal(format!("failed to provision tenant `{}`: {}", tenant, e)),
+    })?;
+
+    let claims = SessionClaims::new(tenant, new_user_id, PrincipalKind::Admin, user_request.role.clone());
+    let session_cookie = session_store::start(&claims, &req).await?;
+    Ok(HttpResponse::Created()
+        .cookie(session_cookie)
+        .json(serde_json::json!({
+            "id": new_user_id,
+            "email": user_request.email,
+            "name": user_request.name,
+            "role": user_request.role
+        })))
+}
 
 
-    // SQL query to create the new database
-    let create_db_sql = format!("CREATE DATABASE {}; ", new_user.database_name);
+pub async fn login_data(pool: web::Data<DbPool>, user_request: Validated<CreateUserLogInRequest>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let account = throttle::account_key("admin", &user_request.email);
+    let ip = throttle::client_ip(&req);
+    throttle::check(&account, &ip).await.map_err(|retry_after| AppError::TooManyAttempts { retry_after })?;
 
-    println!("{}", create_db_sql);
+    let mut conn = pool.get()?;
 
-    // Execute the SQL to create the database
-    match sql_query(create_db_sql).execute(&mut conn) {
-        Ok(_) => {
-            // Log success
-            println!("Database created successfully: {}", new_user.database_name);
-        }
-        Err(e) => {
-            eprintln!("Failed to create database: {:?}", e);
-            return HttpResponse::InternalServerError().json("Failed to create database");
+    let result = adminD::table
+        .filter(email.eq(user_request.email.clone()))
+        .filter(deleted_at.is_null())
+        .first::<LogInUser>(&mut conn)
+        .optional()?;  // Use optional to handle the case where no result is found
+
+    // Unknown email and wrong password are indistinguishable to the caller
+    let verified = verify_or_dummy(&user_request.password, result.as_ref().map(|admin| admin.password.as_str()));
+    let admin_data = match result {
+        Some(admin_data) if verified => admin_data,
+        _ => {
+            let delay = throttle::record_failure(&account, &ip).await;
+            tokio::time::sleep(delay).await;
+            return Err(AppError::InvalidCredentials);
         }
     };
 
-    // Building database URL for the new database
-    dotenv().ok();
-    let database_url_admin = format!("{}{}", env::var("DATABASE_URL_ADMIN").expect("DATABASE_URL must be set"), new_user.database_name);
-    println!("{}",database_url_admin);
-
-    // tokio::time::sleep(Duration::from_secs(1)).await;
-    // Use `spawn_blocking` to prevent blocking the async runtime while connecting to the new database
-    let conn_admin = tokio::task::spawn_blocking(move || {
-        PgConnection::establish(&database_url_admin)
-    })
-        .await
-        .unwrap(); // Ensure you handle errors properly
-
-    match conn_admin {
-        Ok(conn) => {
-            let migration_result = migration(conn).await;
-            get_database(new_user.database_name.clone()).await;
-            let data = login{
-                database_name:new_user.database_name.clone(),
-            };
-            let admin_json = serde_json::to_string(&data).expect("Failed to serialize user");
-            let cookies = cookie::Cookie::build("Data",admin_json).path("/").secure(true).http_only(true).same_site(SameSite::None)
-                .finish();
-            HttpResponse::Created()
-                .cookie(cookies)
-                .json(serde_json::json!({
-        "id": new_user.user_id,
-        "email": new_user.email,
-        "name": new_user.name,
-        "role": new_user.role
-    }))
-
-        }
-        Err(e) => {
-            eprintln!("Failed to connect to the new database: {:?}", e);
-            HttpResponse::InternalServerError().json("Failed to create database")
-        }
+    // With 2FA the failures are only forgotten once the code is accepted too
+    if admin_data.totp_enabled {
+        return two_factor::challenge_response(&admin_data).await;
     }
+    throttle::record_success(&account).await;
+
+    let claims = SessionClaims::new(admin_data.database_name.clone(), admin_data.user_id, PrincipalKind::Admin, admin_data.role.clone());
+    let session_cookie = session_store::start(&claims, &req).await?;
+    Ok(HttpResponse::Ok().cookie(session_cookie).json(serde_json::json!({
+        "id": admin_data.user_id,
+        "name": admin_data.name,
+        "email": admin_data.email,
+        "company_name":admin_data.company_name,
+        "role": admin_data.role
+    })))
 }
 
-
-pub async fn login_data(pool: web::Data<DbPool>, user_request: web::Json<CreateUserLogInRequest>) -> impl Responder {
-
-    let mut conn = match pool.get() {
-        Ok(conn) =>conn,
-        Err(_) => return HttpResponse::InternalServerError().json("Failed to get a database connection"),
+// Closes the company: exports its data, then deletes both databases. Admins only.
+pub async fn close_account(pool: web::Data<DbPool>, settings: web::Data<Settings>, user_request: Validated<CloseAccountRequest>, auth: AuthContext) -> Result<HttpResponse, AppError> {
+    let admin_id = match auth.principal {
+        Principal::Admin { user_id: id } => id,
+        Principal::Employee { .. } | Principal::ApiKey { .. } => {
+            return Err(AppError::Forbidden("Only the company admin can close the account".to_string()));
+        }
     };
 
-
-    let result = adminD::table
-        .filter(email.eq(user_request.email.clone()))
+    let mut conn = pool.get()?;
+    let admin_data = adminD::table
+        .find(admin_id)
+        .filter(deleted_at.is_null())
         .first::<LogInUser>(&mut conn)
-        .optional()  // Use optional to handle the case where no result is found
-        .expect("Error while loading admins data");
+        .optional()?
+        .ok_or_else(|| AppError::not_found("User"))?;
+    drop(conn);
 
-    match result {
-        Some(admin_data) => {
-            if verify(&user_request.password, &admin_data.password).unwrap_or(false) {
-                let database =  login {
-                    database_name : admin_data.database_name.clone(),
-                };
-                let cookie_data = serde_json::to_string(&database).expect("Failed to serialize user");
-                let cookies = cookie::Cookie::build("Data", cookie_data)
-                    .path("/")
-                    .http_only(true)
-                    .same_site(SameSite::None)
-                    .secure(true)
-                    .finish();
-                HttpResponse::Ok().cookie(cookies).json(serde_json::json!({
-                    "id": admin_data.user_id,
-                    "name": admin_data.name,
-                    "email": admin_data.email,
-                    "company_name":admin_data.company_name,
-                    "role": admin_data.role
-                }))
-            } else {
-                HttpResponse::Created().json(CreateUserLogInResponse { message: "Wrong Password".to_string()})
-            }
-        }
-        None => {
-            HttpResponse::InternalServerError().json("Error loading user")
-        }
+    if !verify_or_dummy(&user_request.password, Some(&admin_data.password)) {
+        return Err(AppError::Forbidden("Password is incorrect".to_string()));
+    }
+    if user_request.company_name != admin_data.company_name {
+        return Err(AppError::BadRequest("Company name does not match".to_string()));
     }
-}
 
+    // Nothing is deleted unless the export succeeded
+    let tenant = admin_data.database_name.clone();
+    let archive = deprovision::deprovision(&pool, admin_data, Path::new(&settings.export.dir))
+        .await
+        .map_err(|e| AppError::Internal(format!("failed to close tenant `{}`: {}", tenant, e)))?;
+    Ok(HttpResponse::Ok()
+        .cookie(session::removal_cookie())
+        .content_type("application/zip")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.zip\"", tenant)))
+        .body(archive))
+}
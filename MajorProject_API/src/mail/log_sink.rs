@@ -0,0 +1,40 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::Utc;
+use tracing::info;
+use crate::config::settings::MailSettings;
+use crate::mail::{MailError, MailMessage, MailSender};
+
+/// Development transport: appends every message to a file and logs it.
+pub struct LogMailer {
+    path: PathBuf,
+}
+
+impl LogMailer {
+    /// Writes to `mail.log_path`, by default `mail.log` in the working directory.
+    pub fn from_settings(mail: &MailSettings) -> Self {
+        LogMailer { path: PathBuf::from(&mail.log_path) }
+    }
+}
+
+impl MailSender for LogMailer {
+    fn send(&self, message: &MailMessage) -> Result<(), MailError> {
+        info!("mail to {}: {}", message.to, message.subject);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| MailError::Send(e.to_string()))?;
+        writeln!(
+            file,
+            "--- {}\nTo: {}\nSubject: {}\n\n{}\n",
+            Utc::now().to_rfc3339(),
+            message.to,
+            message.subject,
+            message.body
+        )
+        .map_err(|e| MailError::Send(e.to_string()))
+    }
+}
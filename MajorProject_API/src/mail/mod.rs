@@ -0,0 +1,41 @@
+pub mod smtp;
+pub mod log_sink;
+
+use std::sync::Arc;
+use crate::config::settings::MailSettings;
+
+#[derive(Debug, Clone)]
+pub struct MailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    Config(String),
+    Send(String),
+}
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailError::Config(msg) => write!(f, "mail configuration error: {}", msg),
+            MailError::Send(msg) => write!(f, "failed to send mail: {}", msg),
+        }
+    }
+}
+
+/// Outgoing mail transport. Implementations are blocking; call them through `web::block`.
+pub trait MailSender: Send + Sync {
+    fn send(&self, message: &MailMessage) -> Result<(), MailError>;
+}
+
+/// Pick the transport from `mail.transport` (`smtp` or `log`, default `log`).
+pub fn from_settings(mail: &MailSettings) -> Result<Arc<dyn MailSender>, MailError> {
+    match mail.transport.as_str() {
+        "smtp" => Ok(Arc::new(smtp::SmtpMailer::from_settings(mail)?)),
+        "log" => Ok(Arc::new(log_sink::LogMailer::from_settings(mail))),
+        other => Err(MailError::Config(format!("unknown mail.transport `{}`", other))),
+    }
+}
@@ -0,0 +1,56 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use crate::config::settings::MailSettings;
+use crate::mail::{MailError, MailMessage, MailSender};
+
+/// Sends mail through an SMTP relay (STARTTLS).
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Uses `mail.smtp_host`, `smtp_port`, `smtp_username`, `smtp_password` and `from`.
+    pub fn from_settings(mail: &MailSettings) -> Result<Self, MailError> {
+        let required = |value: &Option<String>, key: &str| {
+            value.clone().ok_or_else(|| MailError::Config(format!("mail.{} must be set", key)))
+        };
+
+        let host = required(&mail.smtp_host, "smtp_host")?;
+        let from = required(&mail.from, "from")?
+            .parse::<Mailbox>()
+            .map_err(|e| MailError::Config(format!("mail.from: {}", e)))?;
+
+        let mut builder = SmtpTransport::starttls_relay(&host)
+            .map_err(|e| MailError::Config(format!("mail.smtp_host: {}", e)))?;
+        if let Some(port) = mail.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&mail.smtp_username, &mail.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+impl MailSender for SmtpMailer {
+    fn send(&self, message: &MailMessage) -> Result<(), MailError> {
+        let to = message
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| MailError::Send(format!("invalid recipient: {}", e)))?;
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(message.subject.clone())
+            .body(message.body.clone())
+            .map_err(|e| MailError::Send(e.to_string()))?;
+
+        self.transport
+            .send(&email)
+            .map(|_| ())
+            .map_err(|e| MailError::Send(e.to_string()))
+    }
+}
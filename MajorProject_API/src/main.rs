@@ -6,54 +6,120 @@ mod schema;
 mod employee_schema;
 mod redis;
 mod Request_microservice;
+mod auth;
+mod mail;
+mod audit;
+mod cli;
+mod tenant;
+mod config;
+mod metrics;
+mod telemetry;
+mod error;
+mod validation;
 
 use actix_web::{web, App, HttpServer};
 use actix_cors::Cors;
-use dotenv::dotenv;
 use std::env;
 use routes::user_routes::init;
+use config::settings::{self, Settings};
 use connect_sql::sql_handler::{establish_connection};
-use env_logger;
+use error::app_error::{reject_input, route_not_found};
+use telemetry::middleware::{RequestTracing, REQUEST_ID, TRACEPARENT};
+use tracing::{error, info, warn};
 
 
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
-    dotenv().ok();
+    let settings = match Settings::load() {
+        Ok(loaded) => settings::install(loaded),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = telemetry::subscriber::init(&settings.log) {
+        eprintln!("Failed to set up logging: {}", err);
+        std::process::exit(1);
+    }
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args, settings).await {
+        std::process::exit(code);
+    }
 
-    // Get host and port from environment variables or use defaults
-    let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "8090".to_string());
+    let host = settings.server.host.clone();
+    let port = settings.server.port;
 
-    // Print the server URL for debugging
-    println!(
-        "🚀 Server is running at http://{}:{}",
-        host, port
-    );
+    info!("Server is running at http://{}:{}", host, port);
 
     let pool = match establish_connection() {
         Ok(pool) => pool,
         Err(err) => {
-            eprintln!("Failed to create DB pool: {}", err);
+            error!("Failed to create DB pool: {}", err);
             return std::process::exit(1);
         }
     };
 
 
-    HttpServer::new(move || {
+    // Bring existing tenants up to date with migrations added since they were created
+    if settings.tenancy.migrate_on_start {
+        let results = match tenant::migrate::all_tenants(&pool) {
+            Ok(tenants) => tenant::migrate::migrate_tenants(tenants, settings.tenancy.migration_concurrency).await,
+            Err(err) => {
+                error!("Failed to list tenants for migration: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let failed = tenant::migrate::log_results(&results);
+        if failed > 0 {
+            warn!("{} of {} tenants failed to migrate; see the log", failed, results.len());
+        }
+    }
+
+    connect_sql::pool_cache::spawn_reaper();
+    metrics::registry::init();
+
+    let mailer = match mail::from_settings(&settings.mail) {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            error!("Failed to configure mail transport: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Scrapes are not authenticated, so they get a listener of their own
+    let metrics_server = HttpServer::new(|| App::new().route("/metrics", web::get().to(handlers::metrics::metrics)))
+        .workers(1)
+        .bind(settings.server.metrics_addr)?
+        .run();
+    info!("Metrics are served at http://{}/metrics", settings.server.metrics_addr);
+
+    let server = HttpServer::new(move || {
+        let cors = settings.server.cors_origins.iter().fold(Cors::default(), |cors, origin| cors.allowed_origin(origin));
         App::new()
             .wrap(
-                Cors::default()
-                    .allowed_origin("http://localhost:5173")
-                    .allowed_methods(vec!["GET", "POST","PATCH"])
-                    .allowed_headers(vec![http::header::CONTENT_TYPE])
+                cors
+                    .allowed_methods(vec!["GET", "POST","PATCH", "DELETE"])
+                    .allowed_headers(vec![http::header::CONTENT_TYPE, http::header::AUTHORIZATION, TRACEPARENT])
+                    .expose_headers(vec![REQUEST_ID])
                     .supports_credentials() // ✅
             )
+            .wrap(metrics::middleware::RequestMetrics)
+            .wrap(RequestTracing)
             .app_data(web::Data::new(pool.clone())) // Share DbPool with handlers
+            .app_data(web::Data::from(mailer.clone()))
+            .app_data(web::Data::new(settings.clone()))
+            .app_data(web::JsonConfig::default().error_handler(reject_input))
+            .app_data(web::QueryConfig::default().error_handler(reject_input))
+            .app_data(web::PathConfig::default().error_handler(reject_input))
             .configure(init) // Load your routes
+            .default_service(web::to(route_not_found))
     })
-        .bind(format!("{}:{}", host, port))?
-        .run()
-        .await
+        .bind((host, port))?
+        .run();
+    tokio::try_join!(server, metrics_server)?;
+
+    telemetry::subscriber::shutdown();
+    Ok(())
 }
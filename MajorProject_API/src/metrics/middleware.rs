@@ -0,0 +1,64 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Instant;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use crate::metrics::registry::{HTTP_DURATION, HTTP_REQUESTS};
+
+/// App middleware counting and timing every request.
+///
+/// Requests are labelled by route pattern (`/api/sessions/{sid}`), not by path,
+/// so ids in URLs do not create a series each. Unrouted requests share `unmatched`.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+            let status = match &result {
+                Ok(response) => response.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+            HTTP_REQUESTS.with_label_values(&labels).inc();
+            HTTP_DURATION.with_label_values(&labels).observe(started.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
@@ -0,0 +1,3 @@
+pub mod registry;
+pub mod middleware;
+pub mod mongo;
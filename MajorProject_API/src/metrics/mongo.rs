@@ -0,0 +1,19 @@
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandSucceededEvent};
+use crate::metrics::registry::STORE_DURATION;
+
+/// Times every command the Mongo client sends, whichever handler issued it.
+pub struct MongoCommandMetrics;
+
+impl CommandEventHandler for MongoCommandMetrics {
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        STORE_DURATION
+            .with_label_values(&["mongo", &event.command_name, "ok"])
+            .observe(event.duration.as_secs_f64());
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        STORE_DURATION
+            .with_label_values(&["mongo", &event.command_name, "error"])
+            .observe(event.duration.as_secs_f64());
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::Instant;
+use tracing::error;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use crate::connect_sql::pool_cache;
+
+/// Every metric the server exports, except the pool gauges taken at scrape time.
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(metric: M) -> M {
+    REGISTRY.register(Box::new(metric.clone())).expect("metric names are unique");
+    metric
+}
+
+fn counter_vec(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    register(IntCounterVec::new(Opts::new(name, help), labels).expect("valid metric"))
+}
+
+fn histogram_vec(name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    register(HistogramVec::new(HistogramOpts::new(name, help), labels).expect("valid metric"))
+}
+
+fn counter(name: &str, help: &str) -> IntCounter {
+    register(IntCounter::new(name, help).expect("valid metric"))
+}
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    counter_vec("http_requests_total", "HTTP requests by route pattern and status", &["method", "route", "status"])
+});
+
+pub static HTTP_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    histogram_vec("http_request_duration_seconds", "HTTP request latency", &["method", "route", "status"])
+});
+
+pub static STORE_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    histogram_vec(
+        "store_operation_duration_seconds",
+        "Mongo and Redis command latency",
+        &["store", "operation", "outcome"],
+    )
+});
+
+pub static SERVICE_CALLS: Lazy<IntCounterVec> = Lazy::new(|| {
+    counter_vec(
+        "service_calls_total",
+        "Calls proxied to the FastAPI services, by outcome (ok, unreachable, bad_response)",
+        &["service", "endpoint", "outcome"],
+    )
+});
+
+pub static SERVICE_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    histogram_vec("service_call_duration_seconds", "Latency of calls to the FastAPI services", &["service", "endpoint"])
+});
+
+pub static SALES_RECORDED: Lazy<IntCounter> = Lazy::new(|| counter("sales_recorded_total", "Sales recorded"));
+
+pub static ORDERS_CREATED: Lazy<IntCounter> = Lazy::new(|| counter("orders_created_total", "Orders placed"));
+
+pub static ORDERS_DELIVERED: Lazy<IntCounter> = Lazy::new(|| counter("orders_delivered_total", "Orders marked delivered"));
+
+/// Register every metric up front so they are exported before their first event.
+pub fn init() {
+    Lazy::force(&HTTP_REQUESTS);
+    Lazy::force(&HTTP_DURATION);
+    Lazy::force(&STORE_DURATION);
+    Lazy::force(&SERVICE_CALLS);
+    Lazy::force(&SERVICE_DURATION);
+    Lazy::force(&SALES_RECORDED);
+    Lazy::force(&ORDERS_CREATED);
+    Lazy::force(&ORDERS_DELIVERED);
+}
+
+/// Time one Mongo or Redis command.
+pub fn observe_store(store: &str, operation: &str, ok: bool, started: Instant) {
+    STORE_DURATION
+        .with_label_values(&[store, operation, if ok { "ok" } else { "error" }])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Outcome of one call to a FastAPI service.
+pub fn observe_service(service: &str, endpoint: &str, outcome: &str, started: Instant) {
+    SERVICE_CALLS.with_label_values(&[service, endpoint, outcome]).inc();
+    SERVICE_DURATION.with_label_values(&[service, endpoint]).observe(started.elapsed().as_secs_f64());
+}
+
+// Pool figures live in the pool cache, so their gauges are built fresh on every scrape
+fn pool_families() -> prometheus::Result<Vec<prometheus::proto::MetricFamily>> {
+    let registry = Registry::new();
+    let tenant_pool = IntGaugeVec::new(
+        Opts::new("tenant_pool_connections", "Open connections across cached tenant pools"),
+        &["state"],
+    )?;
+    let cache_events = IntCounterVec::new(
+        Opts::new("tenant_pool_cache_events_total", "Tenant pool cache lookups and evictions"),
+        &["event"],
+    )?;
+    let cached = IntGaugeVec::new(Opts::new("tenant_pools", "Cached tenant pools and the most allowed"), &["kind"])?;
+    registry.register(Box::new(tenant_pool.clone()))?;
+    registry.register(Box::new(cache_events.clone()))?;
+    registry.register(Box::new(cached.clone()))?;
+
+    // Totals only: tenant database names are not for the scraper
+    let stats = pool_cache::stats();
+    tenant_pool.with_label_values(&["busy"]).set((stats.connections - stats.idle_connections) as i64);
+    tenant_pool.with_label_values(&["idle"]).set(stats.idle_connections as i64);
+    for (event, count) in [
+        ("hit", stats.hits),
+        ("miss", stats.misses),
+        ("evicted_lru", stats.evicted_lru),
+        ("evicted_idle", stats.evicted_idle),
+        ("evicted_explicit", stats.evicted_explicit),
+    ] {
+        cache_events.with_label_values(&[event]).inc_by(count);
+    }
+    cached.with_label_values(&["current"]).set(stats.pools as i64);
+    cached.with_label_values(&["max"]).set(stats.max_pools as i64);
+    Ok(registry.gather())
+}
+
+/// All metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut families = REGISTRY.gather();
+    match pool_families() {
+        Ok(pools) => families.extend(pools),
+        Err(e) => error!("Failed to collect tenant pool metrics: {}", e),
+    }
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&families, &mut buffer) {
+        error!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::employee_schema::api_keys;
+use crate::validation::rules;
+
+#[derive(Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
+    pub name: String,
+    /// Permission names (see `auth::permission`), e.g. `["sales.write", "inventory.read"]`.
+    #[validate(length(min = 1, message = "At least one permission is required"), custom(function = "rules::permission_names"))]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey {
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub permissions: String,
+    pub created_by: i32,
+    pub created_by_kind: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Serialize)]
+pub struct ApiKeyField {
+    pub key_id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub permissions: String,
+    /// `users.user_id` or `employees.employee_id`, depending on `created_by_kind`.
+    pub created_by: i32,
+    /// `admin` or `employee`, like `logs.actor_kind`; `None` for keys made before it was recorded.
+    pub created_by_kind: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,52 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::employee_schema::logs;
+
+#[derive(Insertable)]
+#[diesel(table_name = logs)]
+pub struct NewAuditLog {
+    pub activity: String,
+    pub performed_by: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub actor_kind: String,
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub before_state: Option<Value>,
+    pub after_state: Option<Value>,
+    pub ip: Option<String>,
+    pub prev_hash: Option<String>,
+    pub row_hash: Option<String>,
+}
+
+#[derive(Queryable, Serialize)]
+pub struct AuditLogField {
+    pub log_id: i32,
+    pub activity: String,
+    pub performed_by: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub actor_kind: String,
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub before_state: Option<Value>,
+    pub after_state: Option<Value>,
+    pub ip: Option<String>,
+    pub prev_hash: Option<String>,
+    pub row_hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub actor_kind: Option<String>,
+    pub performed_by: Option<i32>,
+    pub action: Option<String>,
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    /// Inclusive lower bound, e.g. `2025-01-31T00:00:00`.
+    pub from: Option<NaiveDateTime>,
+    /// Exclusive upper bound.
+    pub to: Option<NaiveDateTime>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
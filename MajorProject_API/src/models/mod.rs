@@ -1,3 +1,5 @@
 pub mod user_requests;
 pub mod tools;
 
+pub mod api_keys;
+pub mod audit;
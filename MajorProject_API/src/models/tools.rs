@@ -1,16 +1,33 @@
-use diesel::{deserialize, Insertable, Queryable};
+use diesel::{deserialize, serialize, AsExpression, FromSqlRow, Insertable, Queryable};
+use diesel::deserialize::FromSql;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{IsNull, Output, ToSql};
 use crate::employee_schema::orders as table_orders;
-use crate::employee_schema::sales;
+use crate::employee_schema::{order_lines, order_status_history};
+use crate::employee_schema::sql_types::OrderStatus;
+use crate::employee_schema::{sale_lines, sales};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
 use std::str::FromStr;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use crate::employee_schema::sales::price;
+use validator::{Validate, ValidationError};
+use crate::validation::rules;
 
-#[derive(Debug)]
+/// Where a purchase order is, stored as the Postgres `order_status` enum.
+///
+/// pending → approved → shipped → delivered, with any number of partial
+/// deliveries after shipping. An order can be cancelled until stock arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[diesel(sql_type = OrderStatus)]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
     Pending,
+    Approved,
     Shipped,
+    PartiallyDelivered,
     Delivered,
     Cancelled,
 }
@@ -19,31 +36,126 @@ impl Status {
     pub(crate) fn as_str(&self) -> &'static str {
         match self {
             Status::Pending => "pending",
+            Status::Approved => "approved",
             Status::Shipped => "shipped",
+            Status::PartiallyDelivered => "partially_delivered",
             Status::Delivered => "delivered",
             Status::Cancelled => "cancelled",
         }
     }
+
+    /// Statuses an order in this status may move to.
+    pub fn next(&self) -> &'static [Status] {
+        match self {
+            Status::Pending => &[Status::Approved, Status::Cancelled],
+            Status::Approved => &[Status::Shipped, Status::Cancelled],
+            Status::Shipped => &[Status::PartiallyDelivered, Status::Delivered, Status::Cancelled],
+            Status::PartiallyDelivered => &[Status::PartiallyDelivered, Status::Delivered],
+            Status::Delivered | Status::Cancelled => &[],
+        }
+    }
+
+    pub fn can_become(&self, next: Status) -> bool {
+        self.next().contains(&next)
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Status::Pending),
+            "approved" => Ok(Status::Approved),
+            "shipped" => Ok(Status::Shipped),
+            "partially_delivered" => Ok(Status::PartiallyDelivered),
+            "delivered" => Ok(Status::Delivered),
+            "cancelled" => Ok(Status::Cancelled),
+            _ => Err(format!("Unknown status `{}`, expected pending, approved, shipped, partially_delivered, delivered or cancelled", s)),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ToSql<OrderStatus, Pg> for Status {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<OrderStatus, Pg> for Status {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        Ok(std::str::from_utf8(bytes.as_bytes())?.parse()?)
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct OrdersRequest {
+    #[validate(length(max = 200), custom(function = "rules::not_blank"))]
     pub supplier_name: String,
-    pub categories : Vec<String>,
-    pub price : Vec<f32>,
-    pub products: HashMap<String, i32>,
+    #[validate(length(min = 1, max = 500), nested, custom(function = "rules::distinct_order_products"))]
+    pub lines: Vec<OrderLineRequest>,
+}
 
+#[derive(Serialize, Deserialize, Validate)]
+pub struct OrderLineRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub sku: Option<String>,
+    #[validate(length(max = 200), custom(function = "rules::not_blank"))]
+    pub product_name: String,
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
+    pub category: String,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+    #[validate(custom(function = "rules::price"))]
+    pub unit_cost: f64,
 }
 
 #[derive(Serialize)]
 pub struct OrdersRelatedResponse {
     pub message:String,
+    pub order: OrderWithLines,
+    /// Received stock the inventory did not take; it has to be entered by hand.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unstocked: Vec<UnstockedLine>,
+}
+
+#[derive(Serialize)]
+pub struct UnstockedLine {
+    pub line_id: i32,
+    pub product_name: String,
+    pub quantity: i32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "received_with_partial_delivery"))]
 pub struct StatusChange{
+    #[validate(range(min = 1))]
     pub id : i32,
+    #[validate(custom(function = "rules::order_status"))]
     pub status : String,
+    /// What arrived, for `partially_delivered`. A full delivery receives whatever is outstanding.
+    #[serde(default)]
+    #[validate(length(max = 500), nested)]
+    pub received: Vec<ReceivedLine>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ReceivedLine {
+    #[validate(range(min = 1))]
+    pub line_id: i32,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+}
+
+fn received_with_partial_delivery(change: &StatusChange) -> Result<(), ValidationError> {
+    rules::received_lines(change.status == Status::PartiallyDelivered.as_str(), !change.received.is_empty())
 }
 
 #[derive(Queryable, Insertable, Serialize, Deserialize)]
@@ -51,12 +163,8 @@ pub struct StatusChange{
 pub struct OrderInSQL {
     pub order_id: Option<i32>,
     pub supplier_name: String,
-    pub product_id: Vec<String>,
-    pub categories: Vec<String>,
-    pub quantity_ordered: Vec<i32>,
-    pub price : Vec<f32>,
     pub order_date: Option<NaiveDateTime>,
-    pub status: String,
+    pub status: Status,
 }
 
 #[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
@@ -64,62 +172,257 @@ pub struct OrderInSQL {
 pub struct OrderField {
     pub order_id: i32,
     pub supplier_name: String,
-    pub product_id: Vec<String>,
-    pub categories: Vec<String>,
-    pub quantity_ordered: Vec<i32>,
-    pub price : Vec<f32>,
     pub order_date: Option<NaiveDateTime>,
-    pub status: String,
+    pub status: Status,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = order_lines)]
+pub struct NewOrderLine {
+    pub order_id: i32,
+    pub sku: Option<String>,
+    pub product_name: String,
+    pub category: String,
+    pub quantity: i32,
+    pub unit_cost: f64,
+}
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = order_lines)]
+pub struct OrderLine {
+    pub line_id: i32,
+    pub order_id: i32,
+    pub sku: Option<String>,
+    pub product_name: String,
+    pub category: String,
+    pub quantity: i32,
+    pub unit_cost: f64,
+    pub quantity_received: i32,
+}
 
-/*
-sale_id -> Int4,
-        product_id -> Array<Text>,
-        quantity_sold -> Array<Int4>,
-        price -> Array<Float8>,
-        total_price -> Float8,
-        sold_by -> Int4,
-        sale_date -> Nullable<Timestamp>,
-*/
+#[derive(Insertable)]
+#[diesel(table_name = order_status_history)]
+pub struct NewOrderStatusChange {
+    pub order_id: i32,
+    pub from_status: Option<Status>,
+    pub to_status: Status,
+    pub changed_by: Option<i32>,
+    pub actor_kind: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
 
-#[derive(Deserialize)]
+/// One status transition. `changed_by` is empty for orders placed before transitions were recorded.
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = order_status_history)]
+pub struct OrderStatusChange {
+    pub history_id: i32,
+    pub order_id: i32,
+    pub from_status: Option<Status>,
+    pub to_status: Status,
+    pub changed_by: Option<i32>,
+    pub actor_kind: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+/// An order as the API returns it: the order row, its lines in entry order and
+/// its status history, oldest first.
+#[derive(Serialize, Debug)]
+pub struct OrderWithLines {
+    #[serde(flatten)]
+    pub order: OrderField,
+    pub lines: Vec<OrderLine>,
+    pub history: Vec<OrderStatusChange>,
+}
+
+impl OrderWithLines {
+    /// Load the lines and history of `orders` and attach them, keeping the order of `orders`.
+    pub fn attach(conn: &mut PgConnection, orders: Vec<OrderField>) -> QueryResult<Vec<OrderWithLines>> {
+        let ids: Vec<i32> = orders.iter().map(|order| order.order_id).collect();
+        let mut lines_by_order: HashMap<i32, Vec<OrderLine>> = HashMap::new();
+        for line in order_lines::table
+            .filter(order_lines::order_id.eq_any(&ids))
+            .order(order_lines::line_id.asc())
+            .load::<OrderLine>(conn)?
+        {
+            lines_by_order.entry(line.order_id).or_default().push(line);
+        }
+        let mut history_by_order: HashMap<i32, Vec<OrderStatusChange>> = HashMap::new();
+        for change in order_status_history::table
+            .filter(order_status_history::order_id.eq_any(&ids))
+            .order(order_status_history::history_id.asc())
+            .load::<OrderStatusChange>(conn)?
+        {
+            history_by_order.entry(change.order_id).or_default().push(change);
+        }
+        Ok(orders
+            .into_iter()
+            .map(|order| OrderWithLines {
+                lines: lines_by_order.remove(&order.order_id).unwrap_or_default(),
+                history: history_by_order.remove(&order.order_id).unwrap_or_default(),
+                order,
+            })
+            .collect())
+    }
+}
+
+
+
+#[derive(Deserialize, Validate)]
 pub struct SaleRequest {
-    pub sale_by : i32,
-    pub products: HashMap<String,i32>,
-    pub categories: Vec<String>,
-    pub price: Vec<f64>,
+    #[validate(length(min = 1, max = 500), nested)]
+    pub lines: Vec<SaleLineRequest>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+#[validate(schema(function = "sale_line_discount"))]
+pub struct SaleLineRequest {
+    #[validate(length(max = 200), custom(function = "rules::not_blank"))]
+    pub product_name: String,
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
+    pub category: String,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+    #[validate(custom(function = "rules::price"))]
+    pub unit_price: f64,
+    #[serde(default)]
+    pub discount: f64,
+}
+
+fn sale_line_discount(line: &SaleLineRequest) -> Result<(), ValidationError> {
+    rules::discount(line.discount, line.quantity, line.unit_price)
+}
+
+impl SaleLineRequest {
+    /// Quantity times unit price, less the discount, in whole cents.
+    pub fn line_total(&self) -> f64 {
+        round_cents(f64::from(self.quantity) * self.unit_price - self.discount)
+    }
+}
+
+pub fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
 }
 
 #[derive(Serialize)]
 pub struct SaleRelatedResponse {
     pub message:String,
+    pub sale: SaleWithLines,
+    /// Sold stock the inventory did not give up; it has to be taken out by hand.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unstocked: Vec<UnstockedLine>,
 }
 #[derive(Queryable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = sales)]
 pub struct SaleInSQL {
     pub sale_id: Option<i32>,
-    pub product_id: Vec<String>,
-    pub quantity_sold: Vec<i32>,
-    pub price : Vec<f64>,
     pub total_price : f64,
     pub sold_by : i32,
     pub sale_date: Option<NaiveDateTime>,
-    pub categories: Vec<String>,
+    pub sold_by_kind: Option<String>,
 }
 
 #[derive(Queryable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = sales)]
 pub struct SaleField {
     pub sale_id: i32,
-    pub product_id: Vec<String>,
-    pub quantity_sold: Vec<i32>,
-    pub price: Vec<f64>,
     pub total_price: f64,
+    /// `users.user_id`, `employees.employee_id` or `api_keys.key_id`, depending on `sold_by_kind`.
     pub sold_by: i32,
     pub sale_date: Option<NaiveDateTime>,
-    pub categories: Vec<String>,
+    /// `admin`, `employee` or `api_key`, like `logs.actor_kind`; `None` for sales made before it was recorded.
+    pub sold_by_kind: Option<String>,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = sale_lines)]
+pub struct NewSaleLine {
+    pub sale_id: i32,
+    pub product_name: String,
+    pub category: String,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub discount: f64,
+    pub line_total: f64,
+}
 
+#[derive(Queryable, Serialize, Deserialize)]
+#[diesel(table_name = sale_lines)]
+pub struct SaleLine {
+    pub line_id: i32,
+    pub sale_id: i32,
+    pub product_name: String,
+    pub category: String,
+    pub quantity: i32,
+    pub unit_price: f64,
+    pub discount: f64,
+    pub line_total: f64,
+}
+
+/// A sale as the API returns it: the sale row and its lines in entry order.
+#[derive(Serialize)]
+pub struct SaleWithLines {
+    #[serde(flatten)]
+    pub sale: SaleField,
+    pub lines: Vec<SaleLine>,
+}
+
+impl SaleWithLines {
+    /// Load the lines of `sales` and attach them, keeping the order of `sales`.
+    pub fn attach(conn: &mut PgConnection, sales: Vec<SaleField>) -> QueryResult<Vec<SaleWithLines>> {
+        let ids: Vec<i32> = sales.iter().map(|sale| sale.sale_id).collect();
+        let mut by_sale: HashMap<i32, Vec<SaleLine>> = HashMap::new();
+        for line in sale_lines::table
+            .filter(sale_lines::sale_id.eq_any(&ids))
+            .order(sale_lines::line_id.asc())
+            .load::<SaleLine>(conn)?
+        {
+            by_sale.entry(line.sale_id).or_default().push(line);
+        }
+        Ok(sales
+            .into_iter()
+            .map(|sale| SaleWithLines { lines: by_sale.remove(&sale.sale_id).unwrap_or_default(), sale })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Status; 6] = [
+        Status::Pending,
+        Status::Approved,
+        Status::Shipped,
+        Status::PartiallyDelivered,
+        Status::Delivered,
+        Status::Cancelled,
+    ];
+
+    #[test]
+    fn every_transition_is_allowed_or_rejected_as_documented() {
+        let allowed = [
+            (Status::Pending, Status::Approved),
+            (Status::Pending, Status::Cancelled),
+            (Status::Approved, Status::Shipped),
+            (Status::Approved, Status::Cancelled),
+            (Status::Shipped, Status::PartiallyDelivered),
+            (Status::Shipped, Status::Delivered),
+            (Status::Shipped, Status::Cancelled),
+            (Status::PartiallyDelivered, Status::PartiallyDelivered),
+            (Status::PartiallyDelivered, Status::Delivered),
+        ];
+        for from in ALL {
+            for to in ALL {
+                assert_eq!(from.can_become(to), allowed.contains(&(from, to)), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn statuses_parse_from_their_names() {
+        for status in ALL {
+            assert_eq!(status.as_str().parse::<Status>(), Ok(status));
+        }
+        assert!("canceled".parse::<Status>().is_err());
+    }
+}
@@ -2,26 +2,27 @@ use std::fmt;
 // src/models/user_requests.rs
 use diesel::{Insertable, Queryable};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 use crate::schema::{users};
 use crate::employee_schema::employees;
 use chrono::NaiveDateTime;
 use crate::models::tools::Status;
+use crate::validation::rules;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct CreateUserRequest {
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
     pub name: String,
+    #[validate(email)]
     pub email: String,
+    #[validate(custom(function = "rules::password_policy"))]
     pub password: String,
+    #[validate(length(max = 50), custom(function = "rules::not_blank"))]
     pub role: String,
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
     pub company_name: String,
 }
 
-#[derive(Serialize)]
-pub struct CreateUserResponse {
-    pub message: String,
-}
-
-
 #[derive(Queryable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = users)]
 pub struct User {
@@ -32,32 +33,41 @@ pub struct User {
     pub role: String,
     pub company_name: String,
     pub database_name: String,
-    pub created_at: Option<NaiveDateTime>
+    pub created_at: Option<NaiveDateTime>,
+    pub tenancy: String,
 }
 
 //for login
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Validate)]
 pub struct CreateUserLogInRequest {
+    #[validate(email)]
     pub email: String,
+    #[validate(length(min = 1))]
     pub password: String,
 }
 
-#[derive(Serialize)]
-pub struct CreateUserLogInResponse {
-    pub message: String,
-}
-
 #[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = users)]
 pub struct LogInUser {
     pub user_id: i32,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing)]
     pub password: String,
     pub role: String,
     pub company_name: String,
     pub database_name: String,
     pub created_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    #[serde(skip_serializing)]
+    pub totp_last_used_step: Option<i64>,
+    #[serde(skip_serializing)]
+    pub totp_recovery_codes: Vec<String>,
+    pub deleted_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub tenancy: String,
 }
 
 //for Employee creation/login
@@ -80,6 +90,7 @@ pub struct LoginEmployee {
     pub employee_id:i32,
     pub name: String,
     pub email: String,
+    #[serde(skip_serializing)]
     pub password: String,
     pub permission: String,
     pub first_time_password: bool,
@@ -87,18 +98,26 @@ pub struct LoginEmployee {
 }
 
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct CreateEmployeeRequest {
+    #[validate(length(max = 100), custom(function = "rules::not_blank"))]
     pub name: String,
+    /// Temporary password, replaced by the employee at first login.
+    #[validate(custom(function = "rules::password_policy"))]
     pub password: String,
+    #[validate(email)]
     pub email: String,
+    #[validate(custom(function = "rules::permission_list"))]
     pub permission: String,
 }
 
-#[derive(Deserialize,Serialize)]
+#[derive(Deserialize,Serialize, Validate)]
 pub struct EmployeeLogInRequest {
+    #[validate(email)]
     pub email: String,
+    #[validate(length(min = 1))]
     pub password: String,
+    #[validate(custom(function = "rules::not_blank"))]
     pub company_name: String,
 }
 
@@ -107,8 +126,90 @@ pub struct EmployeeLogInResponse {
     pub message: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct EmployeeAdminControl {
+    #[validate(range(min = 1))]
     pub id: i32,
+    #[validate(custom(function = "rules::permission_list"))]
     pub permission: String,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EmployeeUnlock {
+    #[validate(range(min = 1))]
+    pub id: i32,
+}
+//for password reset
+
+#[derive(Deserialize, Validate)]
+pub struct PasswordResetRequest {
+    #[validate(email)]
+    pub email: String,
+    /// Set for employees, whose accounts live in the company's tenant database.
+    #[validate(custom(function = "rules::not_blank"))]
+    pub company_name: Option<String>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PasswordResetConfirm {
+    #[validate(length(min = 1, max = 256))]
+    pub token: String,
+    #[validate(custom(function = "rules::not_blank"))]
+    pub company_name: Option<String>,
+    #[validate(custom(function = "rules::password_policy"))]
+    pub password: String,
+    #[validate(must_match(other = "password", message = "Password mismatch"))]
+    pub re_password: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+pub struct NewAdminResetToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::employee_schema::password_reset_tokens)]
+pub struct NewEmployeeResetToken {
+    pub employee_id: i32,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+//for two-factor authentication
+
+#[derive(Deserialize, Validate)]
+pub struct TotpCode {
+    /// Six-digit authenticator code, or a recovery code where accepted.
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct TwoFactorLogIn {
+    #[validate(length(min = 1, max = 1024))]
+    pub challenge: String,
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+//for session management
+
+#[derive(Deserialize)]
+pub struct SessionQuery {
+    /// Manage another employee's sessions (requires `employees.admin`).
+    pub employee_id: Option<i32>,
+}
+
+//for closing a company account
+
+#[derive(Deserialize, Validate)]
+pub struct CloseAccountRequest {
+    /// Must repeat the company name exactly, as a confirmation.
+    #[validate(custom(function = "rules::not_blank"))]
+    pub company_name: String,
+    #[validate(length(min = 1))]
+    pub password: String,
+}
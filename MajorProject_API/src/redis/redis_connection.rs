@@ -1,12 +1,133 @@
 use redis::{Client};
-use actix_web::{web, HttpRequest};
+use actix_web::web;
 use serde::{Serialize, Deserialize};
 use serde_json;
 use chrono::prelude::*;
-use crate::connect_sql::sql_handler::LogInUser;
 use actix_web::{Responder,Error};
 use chrono::Local;
 use redis::{AsyncCommands};
+use redis::aio::MultiplexedConnection;
+use redis::aio::ConnectionLike;
+use redis::{Arg, AsyncConnectionConfig, Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use once_cell::sync::Lazy;
+use std::time::{Duration, Instant};
+use crate::config::settings;
+use crate::metrics::registry::observe_store;
+use crate::telemetry::trace_context;
+
+static REDIS_CLIENT: Lazy<RedisResult<Client>> = Lazy::new(|| {
+    Client::open(settings::get().redis.url.as_str())
+});
+
+/// How long to answer "unavailable" after a failed connect before trying again.
+const RECONNECT_AFTER: Duration = Duration::from_secs(1);
+
+/// The one multiplexed connection every caller shares, tagged with a generation
+/// so a caller that saw it break only drops that connection, not its replacement.
+#[derive(Default)]
+struct Shared {
+    connection: Option<(u64, MultiplexedConnection)>,
+    generation: u64,
+    failed_at: Option<Instant>,
+}
+
+static SHARED: Lazy<tokio::sync::Mutex<Shared>> = Lazy::new(Default::default);
+
+/// A multiplexed connection that records the latency of every command.
+#[derive(Clone)]
+pub struct TimedConnection {
+    inner: MultiplexedConnection,
+    generation: u64,
+}
+
+impl TimedConnection {
+    // A dropped connection is not reopened by the multiplexer; the next caller connects again
+    async fn forget_if_broken(&self, err: &RedisError) {
+        if err.is_connection_dropped() || err.is_connection_refusal() || err.is_io_error() {
+            let mut shared = SHARED.lock().await;
+            if matches!(shared.connection, Some((generation, _)) if generation == self.generation) {
+                shared.connection = None;
+            }
+        }
+    }
+}
+
+fn command_name(cmd: &Cmd) -> String {
+    match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_lowercase(),
+        _ => "unknown".to_string(),
+    }
+}
+
+impl ConnectionLike for TimedConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.req_packed_command(cmd).await;
+            observe_store("redis", &command_name(cmd), result.is_ok(), started);
+            if let Err(err) = &result {
+                self.forget_if_broken(err).await;
+            }
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(&'a mut self, cmd: &'a Pipeline, offset: usize, count: usize) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.req_packed_commands(cmd, offset, count).await;
+            observe_store("redis", "pipeline", result.is_ok(), started);
+            if let Err(err) = &result {
+                self.forget_if_broken(err).await;
+            }
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
+
+/// A handle on the shared multiplexed connection to Redis, opened on first use.
+///
+/// Connecting times out quickly, and for a second after a failed attempt callers
+/// are answered at once, so those with an in-memory fallback are not held up
+/// when Redis is down.
+pub async fn get_redis_connection() -> RedisResult<TimedConnection> {
+    let mut shared = SHARED.lock().await;
+    if let Some((generation, inner)) = &shared.connection {
+        return Ok(TimedConnection { inner: inner.clone(), generation: *generation });
+    }
+    if shared.failed_at.is_some_and(|failed_at| failed_at.elapsed() < RECONNECT_AFTER) {
+        return Err(RedisError::from((ErrorKind::IoError, "Redis unavailable, retrying shortly")));
+    }
+
+    let client = REDIS_CLIENT
+        .as_ref()
+        .map_err(|e| RedisError::from((ErrorKind::InvalidClientConfig, "Invalid REDIS_URL", e.to_string())))?;
+    let started = Instant::now();
+    let connection = client
+        .get_multiplexed_async_connection_with_config(
+            &AsyncConnectionConfig::new()
+                .set_connection_timeout(Duration::from_secs(1))
+                .set_response_timeout(Duration::from_secs(1)),
+        )
+        .await;
+    observe_store("redis", "connect", connection.is_ok(), started);
+    match connection {
+        Ok(inner) => {
+            shared.generation += 1;
+            shared.failed_at = None;
+            shared.connection = Some((shared.generation, inner.clone()));
+            Ok(TimedConnection { inner, generation: shared.generation })
+        }
+        Err(err) => {
+            shared.failed_at = Some(Instant::now());
+            Err(err)
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 struct Data {
@@ -17,33 +138,26 @@ struct Data {
 }
 
 
-pub async fn send_data_to_ai(product : String , quentity : i32,req: HttpRequest) -> Result<impl Responder, Error> {
-    let client = Client::open("redis://127.0.0.1/").map_err(actix_web::error::ErrorInternalServerError)?;
-
-    let mut con = client.get_multiplexed_async_connection().await
+pub async fn send_data_to_ai(product : String , quentity : i32, tenant: &str) -> Result<impl Responder, Error> {
+    let mut con = get_redis_connection().await
         .map_err(actix_web::error::ErrorInternalServerError)?;
 
-    let database_name = req.cookie("Data")
-        .and_then(|cookie| serde_json::from_str::<LogInUser>(cookie.value()).ok());
-
-    if let Some(user) = database_name {
-        let send_data = Data {
-            database_name: user.database_name.clone(),
-            product,
-            quentity,
-            date: Local::now(),
-        };
+    let send_data = Data {
+        database_name: tenant.to_string(),
+        product,
+        quentity,
+        date: Local::now(),
+    };
 
-        let json_data = serde_json::to_string(&send_data)
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+    let json_data = serde_json::to_string(&send_data)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
 
-        // Use Redis stream with multiplexed connection
-        con.xadd("mystream", "*", &[("data", json_data)])
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?;
+    // Use Redis stream with multiplexed connection
+    // The consumer can tie its work back to the request that recorded the sale
+    let traceparent = trace_context::traceparent().unwrap_or_default();
+    con.xadd::<_, _, _, _, ()>("mystream", "*", &[("data", json_data), ("traceparent", traceparent)])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
 
-        Ok(web::Json("Data sent to Redis successfully"))
-    } else {
-        Err(actix_web::error::ErrorBadRequest("Missing or invalid user data cookie"))
-    }
+    Ok(web::Json("Data sent to Redis successfully"))
 }
\ No newline at end of file
@@ -1,31 +1,105 @@
 use actix_web::web;
-use crate::handlers::user_handler::{create_user,login_data};
-use crate::handlers::employee_handler::{employee_add, employee_login, password_change, update_employee_permission,show_all_employee};
+use crate::auth::guard::RequirePermission;
+use crate::auth::permission::Permission;
+use crate::handlers::user_handler::{close_account, create_user, login_data};
+use crate::handlers::two_factor::{activate_totp, disable_totp, enroll_totp, login_two_factor};
+use crate::handlers::health::{health, ready};
+use crate::handlers::audit::{list_audit_logs, verify_audit_logs};
+use crate::handlers::api_keys::{create_api_key, list_api_keys, revoke_api_key};
+use crate::handlers::sessions::{list_sessions, logout, revoke_all_sessions, revoke_session};
+use crate::handlers::password_reset::{confirm_password_reset, request_password_reset};
+use crate::handlers::employee_handler::{employee_add, employee_login, password_change, update_employee_permission,show_all_employee, unlock_employee};
 use crate::handlers::tools::{set_orders, display_orders, status_change, set_sales, show_all_sales, get_inventory};
 use crate::Request_microservice::request::{analytics_data, category_summary, daily_sales_summary, gen_ai, low_stock_count, product_summary};
 
 pub fn init(cfg: &mut web::ServiceConfig) {
+    // load balancer probes, outside /api; /metrics has its own listener
+    cfg.route("/health", web::get().to(health))
+        .route("/ready", web::get().to(ready));
     cfg.service(
         web::scope("/api")
+            // public
             .route("/users", web::post().to(create_user)) //check-
             .route("/login", web::post().to(login_data))  //check-
-            .route("/employee-add", web::post().to(employee_add)) //check-
+            .route("/login/2fa", web::post().to(login_two_factor))
             .route("/employee_login", web::post().to(employee_login)) //check-
-            .route("/employee-update", web::patch().to(update_employee_permission))
+            .route("/password-reset/request", web::post().to(request_password_reset))
+            .route("/password-reset/confirm", web::post().to(confirm_password_reset))
+            // any signed-in principal; the handlers check whose password / sessions are touched
             .route("/password_change", web::patch().to(password_change))//check-
-            .route("/set-orders", web::post().to(set_orders)) //check-
-            .route("/display-orders", web::get().to(display_orders)) //check-
-            .route("/status-change", web::patch().to(status_change))//check-
-            .route("/sale_set",web::post().to(set_sales))//check
-            .route("/show-sales",web::get().to(show_all_sales))
-            .route("/show-all-emp",web::get().to(show_all_employee)) //check-
-            .route("/get_inventory",web::get().to(get_inventory)) //check-
-            .route("/analytics_data",web::get().to(analytics_data))
-            .route("/low-stock-count",web::get().to(low_stock_count))
-            .route("/daily_sales_summary",web::get().to(daily_sales_summary))
-            .route("/category_summary",web::get().to(category_summary))
-            .route("/product_summary",web::get().to(product_summary))
-            .route("/gen_ai",web::post().to(gen_ai))
+            .route("/logout", web::post().to(logout))
+            .route("/sessions", web::get().to(list_sessions))
+            .route("/sessions/revoke-all", web::post().to(revoke_all_sessions))
+            .route("/sessions/{sid}", web::delete().to(revoke_session))
+            // company admins only; the handlers reject employees
+            .route("/account/close", web::post().to(close_account))
+            .route("/2fa/enroll", web::post().to(enroll_totp))
+            .route("/2fa/activate", web::post().to(activate_totp))
+            .route("/2fa/disable", web::post().to(disable_totp))
+            // employees.admin
+            .service(web::resource("/employee-add")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::post().to(employee_add))) //check-
+            .service(web::resource("/employee-update")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::patch().to(update_employee_permission)))
+            .service(web::resource("/employee-unlock")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::post().to(unlock_employee)))
+            .service(web::resource("/show-all-emp")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::get().to(show_all_employee))) //check-
+            .service(web::resource("/api-keys")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::post().to(create_api_key))
+                .route(web::get().to(list_api_keys)))
+            .service(web::resource("/api-keys/{id}")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::delete().to(revoke_api_key)))
+            .service(web::resource("/audit-logs")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::get().to(list_audit_logs)))
+            .service(web::resource("/audit-logs/verify")
+                .wrap(RequirePermission::new(&[Permission::EmployeesAdmin]))
+                .route(web::get().to(verify_audit_logs)))
+            // orders
+            .service(web::resource("/set-orders")
+                .wrap(RequirePermission::new(&[Permission::OrdersWrite]))
+                .route(web::post().to(set_orders))) //check-
+            .service(web::resource("/display-orders")
+                .wrap(RequirePermission::new(&[Permission::OrdersRead]))
+                .route(web::get().to(display_orders))) //check-
+            .service(web::resource("/status-change")
+                .wrap(RequirePermission::new(&[Permission::OrdersWrite]))
+                .route(web::patch().to(status_change)))//check-
+            // sales & inventory
+            .service(web::resource("/sale_set")
+                .wrap(RequirePermission::new(&[Permission::SalesWrite]))
+                .route(web::post().to(set_sales)))//check
+            .service(web::resource("/show-sales")
+                .wrap(RequirePermission::new(&[Permission::SalesRead]))
+                .route(web::get().to(show_all_sales)))
+            .service(web::resource("/get_inventory")
+                .wrap(RequirePermission::new(&[Permission::InventoryRead]))
+                .route(web::get().to(get_inventory))) //check-
+            // analytics & AI
+            .service(web::resource("/analytics_data")
+                .wrap(RequirePermission::new(&[Permission::AnalyticsRead]))
+                .route(web::get().to(analytics_data)))
+            .service(web::resource("/low-stock-count")
+                .wrap(RequirePermission::new(&[Permission::AnalyticsRead]))
+                .route(web::get().to(low_stock_count)))
+            .service(web::resource("/daily_sales_summary")
+                .wrap(RequirePermission::new(&[Permission::AnalyticsRead]))
+                .route(web::get().to(daily_sales_summary)))
+            .service(web::resource("/category_summary")
+                .wrap(RequirePermission::new(&[Permission::AnalyticsRead]))
+                .route(web::get().to(category_summary)))
+            .service(web::resource("/product_summary")
+                .wrap(RequirePermission::new(&[Permission::AnalyticsRead]))
+                .route(web::get().to(product_summary)))
+            .service(web::resource("/gen_ai")
+                .wrap(RequirePermission::new(&[Permission::GenAiUse]))
+                .route(web::post().to(gen_ai)))
     );
 }
-
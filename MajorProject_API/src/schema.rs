@@ -15,5 +15,30 @@ diesel::table! {
         #[max_length = 255]
         database_name -> Varchar,
         created_at -> Nullable<Timestamp>,
+        totp_secret -> Nullable<Text>,
+        totp_enabled -> Bool,
+        totp_last_used_step -> Nullable<Int8>,
+        totp_recovery_codes -> Array<Text>,
+        deleted_at -> Nullable<Timestamp>,
+        tenancy -> Text,
     }
 }
+
+diesel::table! {
+    password_reset_tokens (token_id) {
+        token_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::joinable!(password_reset_tokens -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    password_reset_tokens,
+    users,
+);
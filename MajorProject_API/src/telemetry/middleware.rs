@@ -0,0 +1,104 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Instant;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use tracing::field::Empty;
+use tracing::{info, info_span, warn, Instrument};
+use crate::telemetry::trace_context::{self, TraceContext};
+
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// App middleware giving every request a span and a request id.
+///
+/// An incoming W3C `traceparent` is continued, otherwise a new trace starts. The
+/// trace id is the request id: it is on every log line of the request (with the
+/// tenant and principal once authenticated), is passed on to the FastAPI services
+/// and the Redis stream, and is echoed in the `X-Request-Id` response header.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let started = Instant::now();
+        let incoming = req
+            .headers()
+            .get(&TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::parse);
+        let span = info_span!(
+            "http_request",
+            method = %req.method(),
+            route = %req.match_pattern().unwrap_or_else(|| "unmatched".to_string()),
+            request_id = Empty,
+            tenant = Empty,
+            principal = Empty,
+        );
+        let context = trace_context::start(&span, incoming.as_ref());
+        span.record("request_id", context.trace_id.as_str());
+
+        Box::pin(
+            async move {
+                let result = trace_context::scope(context.clone(), service.call(req)).await;
+                let latency_ms = started.elapsed().as_millis() as u64;
+                match result {
+                    Ok(mut response) => {
+                        let status = response.status().as_u16();
+                        if response.status().is_server_error() {
+                            warn!(status, latency_ms, "request failed");
+                        } else {
+                            info!(status, latency_ms, "request finished");
+                        }
+                        let headers = response.headers_mut();
+                        if let Ok(value) = HeaderValue::from_str(&context.trace_id) {
+                            headers.insert(REQUEST_ID, value);
+                        }
+                        if let Ok(value) = HeaderValue::from_str(&context.header()) {
+                            headers.insert(TRACEPARENT, value);
+                        }
+                        Ok(response)
+                    }
+                    Err(err) => {
+                        let status = err.as_response_error().status_code().as_u16();
+                        warn!(status, latency_ms, error = %err, "request failed");
+                        Err(err)
+                    }
+                }
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,5 @@
+pub mod subscriber;
+pub mod trace_context;
+pub mod middleware;
+#[cfg(feature = "otlp")]
+pub mod otlp;
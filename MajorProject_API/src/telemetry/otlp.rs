@@ -0,0 +1,59 @@
+use once_cell::sync::OnceCell;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider};
+use opentelemetry::Context;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::Span;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+use crate::telemetry::trace_context::TraceContext;
+
+static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+/// Layer exporting spans over OTLP/HTTP to `endpoint`, e.g. `http://localhost:4318`.
+pub fn layer<S>(endpoint: &str, service_name: &str) -> Result<OpenTelemetryLayer<S, SdkTracer>, String>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+        .build();
+    let tracer = provider.tracer("smart-inventory-api");
+    let _ = PROVIDER.set(provider);
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Flush spans still buffered for export.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::error!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Make `span` continue `incoming` and return its exported ids.
+/// `None` when spans are not being exported.
+pub fn link(span: &Span, incoming: Option<&TraceContext>) -> Option<TraceContext> {
+    if let Some(parent) = incoming {
+        if let (Ok(trace_id), Ok(span_id)) = (TraceId::from_hex(&parent.trace_id), SpanId::from_hex(&parent.span_id)) {
+            let flags = if parent.sampled { TraceFlags::SAMPLED } else { TraceFlags::default() };
+            let remote = SpanContext::new(trace_id, span_id, flags, true, TraceState::default());
+            span.set_parent(Context::new().with_remote_span_context(remote));
+        }
+    }
+    let context = span.context();
+    let exported = context.span().span_context().clone();
+    exported.is_valid().then(|| TraceContext {
+        trace_id: exported.trace_id().to_string(),
+        span_id: exported.span_id().to_string(),
+        sampled: exported.is_sampled(),
+    })
+}
@@ -0,0 +1,38 @@
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+use crate::config::settings::LogSettings;
+
+/// Install the global subscriber: JSON (or plain text) lines on stdout, filtered by
+/// `RUST_LOG` or `log.level`, plus OTLP export when built with the `otlp` feature
+/// and `log.otlp_endpoint` is set. `log` records from dependencies are forwarded.
+pub fn init(settings: &LogSettings) -> Result<(), String> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&settings.level))
+        .map_err(|e| format!("invalid log level `{}`: {}", settings.level, e))?;
+    let output = match settings.format.as_str() {
+        "text" => fmt::layer().boxed(),
+        _ => fmt::layer().json().with_current_span(true).with_span_list(false).boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(output);
+
+    #[cfg(feature = "otlp")]
+    let registry = registry.with(match &settings.otlp_endpoint {
+        Some(endpoint) => Some(crate::telemetry::otlp::layer(endpoint, &settings.service_name)?),
+        None => None,
+    });
+
+    registry.try_init().map_err(|e| e.to_string())?;
+
+    #[cfg(not(feature = "otlp"))]
+    if settings.otlp_endpoint.is_some() {
+        tracing::warn!("log.otlp_endpoint is set but this build has no OTLP support; rebuild with `--features otlp`");
+    }
+    Ok(())
+}
+
+/// Flush telemetry before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    crate::telemetry::otlp::shutdown();
+}
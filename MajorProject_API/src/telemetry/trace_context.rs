@@ -0,0 +1,94 @@
+use std::future::Future;
+use tracing::Span;
+
+/// W3C trace context of the request being handled; its trace id doubles as the request id.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits.
+    pub trace_id: String,
+    /// 16 lowercase hex digits, the id outgoing calls name as their parent.
+    pub span_id: String,
+    pub sampled: bool,
+}
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+fn is_hex_id(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && value.bytes().any(|b| b != b'0')
+}
+
+fn random_id(len: usize) -> String {
+    loop {
+        let id = format!("{:032x}", rand::random::<u128>())[..len].to_string();
+        if is_hex_id(&id, len) {
+            return id;
+        }
+    }
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header, e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    pub fn parse(header: &str) -> Option<TraceContext> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        // Version 00 has exactly four fields; later versions may append more
+        if version == "ff" || version.len() != 2 || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !is_hex_id(trace_id, 32) || !is_hex_id(span_id, 16) || flags.len() != 2 {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(TraceContext {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            sampled: flags & 1 == 1,
+        })
+    }
+
+    /// A new span in the trace of `parent`, or the root of a new trace.
+    pub fn child_of(parent: Option<&TraceContext>) -> TraceContext {
+        TraceContext {
+            trace_id: parent.map(|p| p.trace_id.clone()).unwrap_or_else(|| random_id(32)),
+            span_id: random_id(16),
+            sampled: parent.map(|p| p.sampled).unwrap_or(true),
+        }
+    }
+
+    /// The `traceparent` header naming this context as parent.
+    pub fn header(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, if self.sampled { "01" } else { "00" })
+    }
+}
+
+/// The trace context for a request handled under `span`, continuing `incoming` if given.
+///
+/// With OTLP export enabled the ids are the exported span's, so logs, response
+/// headers and the collector agree.
+pub fn start(span: &Span, incoming: Option<&TraceContext>) -> TraceContext {
+    #[cfg(feature = "otlp")]
+    if let Some(context) = crate::telemetry::otlp::link(span, incoming) {
+        return context;
+    }
+    let _ = span;
+    TraceContext::child_of(incoming)
+}
+
+/// Run `future` with `context` as the current trace context.
+pub async fn scope<F: Future>(context: TraceContext, future: F) -> F::Output {
+    CURRENT.scope(context, future).await
+}
+
+/// Trace context of the request being handled on this task, if any.
+pub fn current() -> Option<TraceContext> {
+    CURRENT.try_with(Clone::clone).ok()
+}
+
+/// `traceparent` header value for calls made on behalf of the current request.
+pub fn traceparent() -> Option<String> {
+    current().map(|context| context.header())
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+use chrono::Utc;
+use diesel::prelude::*;
+use tracing::{error, info};
+use crate::auth::session::PrincipalKind;
+use crate::auth::session_store;
+use crate::connect_sql::no_sql;
+use crate::connect_sql::sql_handler::{establish_connection_to_user_db, DbPool};
+use crate::models::user_requests::LogInUser;
+use crate::schema::users;
+use crate::tenant::export::{self, export_tables};
+use crate::tenant::provision::{blocking, drop_storage};
+use crate::tenant::tenancy::Tenancy;
+
+/// Close a company account and return its data as a zip archive.
+///
+/// Nothing is deleted until the export has been written under `export_dir`.
+/// After the admin row is marked deleted the account is gone as far as users
+/// are concerned; failures dropping the databases are logged for manual cleanup.
+pub async fn deprovision(pool: &DbPool, admin: LogInUser, export_dir: &Path) -> Result<Vec<u8>, String> {
+    let tenant = admin.database_name.clone();
+
+    let export_tenant = tenant.clone();
+    let mut tables = blocking(move || {
+        let tenant_pool = establish_connection_to_user_db(&export_tenant).map_err(|e| format!("{:?}", e))?;
+        let mut conn = tenant_pool.get().map_err(|e| e.to_string())?;
+        export_tables(&mut conn)
+    }).await?;
+    tables.add_inventory(&tenant).await?;
+    let employee_ids = tables.employee_ids.clone();
+    let archive = tables.into_zip(&tenant, &admin.company_name)?;
+    let saved_to = export::save(export_dir, &tenant, &archive)?;
+    info!("Exported tenant `{}` to {}", tenant, saved_to.display());
+
+    let mark_pool = pool.clone();
+    let admin_id = admin.user_id;
+    blocking(move || {
+        let mut conn = mark_pool.get().map_err(|e| e.to_string())?;
+        diesel::update(users::table.find(admin_id))
+            .set(users::deleted_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }).await?;
+
+    let principals = std::iter::once((PrincipalKind::Admin, admin_id))
+        .chain(employee_ids.into_iter().map(|id| (PrincipalKind::Employee, id)));
+    for (kind, id) in principals {
+        if let Err(e) = session_store::revoke_all(&tenant, kind, id, None).await {
+            error!("Manual cleanup needed: failed to revoke sessions of {:?} {} in `{}`: {}", kind, id, tenant, e);
+        }
+    }
+
+    let tenancy = admin.tenancy.parse().unwrap_or(Tenancy::Database);
+    if let Err(e) = drop_storage(pool, &tenant, tenancy).await {
+        error!("Manual cleanup needed: failed to drop {} `{}`: {}", tenancy, tenant, e);
+    }
+    if let Err(e) = no_sql::drop_database(&tenant).await {
+        error!("Manual cleanup needed: failed to drop Mongo database `{}`: {}", tenant, e);
+    }
+
+    info!("Closed tenant `{}` (user {})", tenant, admin_id);
+    Ok(archive)
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use diesel::prelude::*;
+use futures::stream::TryStreamExt;
+use mongodb::bson::Document;
+use serde::Serialize;
+use serde_json::json;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+use crate::connect_sql::no_sql::get_mongo_client;
+use crate::employee_schema::{employees, logs, orders, sales};
+use crate::models::audit::AuditLogField;
+use crate::models::tools::{OrderField, OrderWithLines, SaleField, SaleWithLines};
+use crate::models::user_requests::LoginEmployee;
+
+/// Everything a closed company gets back. Secrets (password hashes, API keys,
+/// reset tokens) are left out.
+pub struct TenantExport {
+    pub employee_ids: Vec<i32>,
+    files: Vec<(&'static str, String)>,
+}
+
+fn to_json<T: Serialize>(rows: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(rows).map_err(|e| e.to_string())
+}
+
+/// Read the tenant's Postgres tables. Blocking.
+pub fn export_tables(conn: &mut PgConnection) -> Result<TenantExport, String> {
+    let employee_rows = employees::table
+        .order(employees::employee_id.asc())
+        .load::<LoginEmployee>(conn)
+        .map_err(|e| e.to_string())?;
+    let order_rows = orders::table.order(orders::order_id.asc()).load::<OrderField>(conn).map_err(|e| e.to_string())?;
+    let order_rows = OrderWithLines::attach(conn, order_rows).map_err(|e| e.to_string())?;
+    let sale_rows = sales::table.order(sales::sale_id.asc()).load::<SaleField>(conn).map_err(|e| e.to_string())?;
+    let sale_rows = SaleWithLines::attach(conn, sale_rows).map_err(|e| e.to_string())?;
+    let log_rows = logs::table.order(logs::log_id.asc()).load::<AuditLogField>(conn).map_err(|e| e.to_string())?;
+
+    let employee_json: Vec<_> = employee_rows
+        .iter()
+        .map(|emp| json!({
+            "employee_id": emp.employee_id,
+            "name": emp.name,
+            "email": emp.email,
+            "permission": emp.permission,
+            "created_at": emp.created_at,
+        }))
+        .collect();
+
+    Ok(TenantExport {
+        employee_ids: employee_rows.iter().map(|emp| emp.employee_id).collect(),
+        files: vec![
+            ("employees.json", to_json(&employee_json)?),
+            ("orders.json", to_json(&order_rows)?),
+            ("sales.json", to_json(&sale_rows)?),
+            ("logs.json", to_json(&log_rows)?),
+        ],
+    })
+}
+
+impl TenantExport {
+    /// Add the Mongo `inventory` collection.
+    pub async fn add_inventory(&mut self, tenant: &str) -> Result<(), String> {
+        let collection = get_mongo_client().await.database(tenant).collection::<Document>("inventory");
+        let items: Vec<Document> = collection
+            .find(None, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?;
+        self.files.push(("inventory.json", to_json(&items)?));
+        Ok(())
+    }
+
+    /// Zip the export, with a `manifest.json` describing it.
+    pub fn into_zip(self, tenant: &str, company_name: &str) -> Result<Vec<u8>, String> {
+        let manifest = json!({
+            "tenant": tenant,
+            "company_name": company_name,
+            "exported_at": Utc::now().naive_utc(),
+            "files": self.files.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+        });
+
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let manifest = to_json(&manifest)?;
+        for (name, contents) in std::iter::once(("manifest.json", &manifest)).chain(self.files.iter().map(|(n, c)| (*n, c))) {
+            archive.start_file(name, options).map_err(|e| e.to_string())?;
+            archive.write_all(contents.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        archive.finish().map(Cursor::into_inner).map_err(|e| e.to_string())
+    }
+}
+
+/// Keep a copy under `dir`, the `export.dir` setting, in case the download is lost.
+pub fn save(dir: &Path, tenant: &str, archive: &[u8]) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}_{}.zip", tenant, Utc::now().format("%Y%m%d%H%M%S")));
+    fs::write(&path, archive).map_err(|e| e.to_string())?;
+    Ok(path)
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use diesel::prelude::*;
+use tracing::{error, info};
+use tokio::sync::Semaphore;
+use crate::connect_sql::sql_handler::DbPool;
+use crate::schema::users;
+use crate::tenant::provision::run_migrations;
+
+#[derive(Debug)]
+pub struct TenantMigration {
+    pub tenant: String,
+    /// Versions applied by this run; empty if the tenant was already up to date.
+    pub applied: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Every live tenant database, from the admin `users` table.
+pub fn all_tenants(pool: &DbPool) -> Result<Vec<String>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    users::table
+        .filter(users::deleted_at.is_null())
+        .select(users::database_name)
+        .order(users::database_name.asc())
+        .load::<String>(&mut conn)
+        .map_err(|e| e.to_string())
+}
+
+/// Apply pending `Employee` migrations to each tenant, at most `concurrency` at a time.
+/// One tenant failing does not stop the others; results come back in input order.
+pub async fn migrate_tenants(tenants: Vec<String>, concurrency: usize) -> Vec<TenantMigration> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let tasks: Vec<_> = tenants
+        .into_iter()
+        .map(|tenant| {
+            let permits = permits.clone();
+            let name = tenant.clone();
+            let task = tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await.expect("semaphore is never closed");
+                let migrate_tenant = tenant.clone();
+                let result = tokio::task::spawn_blocking(move || run_migrations(&migrate_tenant)).await;
+                match result {
+                    Ok(Ok(applied)) => TenantMigration { tenant, applied, error: None },
+                    Ok(Err(e)) => TenantMigration { tenant, applied: Vec::new(), error: Some(e.to_string()) },
+                    Err(e) => TenantMigration { tenant, applied: Vec::new(), error: Some(e.to_string()) },
+                }
+            });
+            (name, task)
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (tenant, task) in tasks {
+        results.push(task.await.unwrap_or_else(|e| TenantMigration {
+            tenant,
+            applied: Vec::new(),
+            error: Some(e.to_string()),
+        }));
+    }
+    results
+}
+
+/// Log one line per tenant and return how many failed.
+pub fn log_results(results: &[TenantMigration]) -> usize {
+    let mut failed = 0;
+    for result in results {
+        match &result.error {
+            Some(e) => {
+                failed += 1;
+                error!("Tenant `{}`: migration failed: {}", result.tenant, e);
+            }
+            None if result.applied.is_empty() => info!("Tenant `{}`: up to date", result.tenant),
+            None => info!("Tenant `{}`: applied {}", result.tenant, result.applied.join(", ")),
+        }
+    }
+    failed
+}
@@ -0,0 +1,7 @@
+pub mod naming;
+pub mod provision;
+pub mod export;
+pub mod deprovision;
+pub mod migrate;
+pub mod tenancy;
+pub mod relocate;
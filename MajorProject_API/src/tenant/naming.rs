@@ -0,0 +1,100 @@
+use chrono::Utc;
+use rand::Rng;
+
+/// Postgres truncates identifiers to 63 bytes; Mongo allows 63 characters for a database name.
+pub const MAX_DATABASE_NAME_LEN: usize = 63;
+// Leaves room for `_<timestamp>_<suffix>`
+const MAX_SLUG_LEN: usize = 40;
+
+/// Lowercase ASCII letters, digits and `_`, starting with a letter.
+pub fn is_valid_database_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+        && name.len() <= MAX_DATABASE_NAME_LEN
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn slug(company_name: &str) -> String {
+    let mut slug = String::new();
+    for c in company_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            // Quotes, hyphens, spaces and non-ASCII all collapse into one separator
+            slug.push('_');
+        }
+        if slug.len() == MAX_SLUG_LEN {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('_');
+    match slug.chars().next() {
+        Some(c) if c.is_ascii_lowercase() => slug.to_string(),
+        Some(_) => format!("t_{}", slug),
+        None => "tenant".to_string(),
+    }
+}
+
+/// Generate a fresh tenant database name from a company name,
+/// e.g. `acme_corp_20250127105503_k3f9`.
+pub fn database_name(company_name: &str) -> String {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(4)
+        .map(|c| (c as char).to_ascii_lowercase())
+        .collect();
+    format!("{}_{}_{}", slug(company_name), Utc::now().format("%Y%m%d%H%M%S"), suffix)
+}
+
+/// Quote an identifier for Postgres DDL. Names are validated as well; this is belt and braces.
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_ident_doubles_quotes() {
+        assert_eq!(quote_ident("acme"), "\"acme\"");
+        assert_eq!(quote_ident("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_ident("x\"; DROP DATABASE y; --"), "\"x\"\"; DROP DATABASE y; --\"");
+    }
+
+    #[test]
+    fn slug_collapses_separators() {
+        assert_eq!(slug("Acme Corp"), "acme_corp");
+        assert_eq!(slug("O'Brien & Sons, Ltd."), "o_brien_sons_ltd");
+        assert_eq!(slug("  --Ünïcode-- "), "n_code");
+    }
+
+    #[test]
+    fn slug_starts_with_a_letter() {
+        assert_eq!(slug("3M"), "t_3m");
+        assert_eq!(slug("!!!"), "tenant");
+        assert_eq!(slug(""), "tenant");
+    }
+
+    #[test]
+    fn slug_is_truncated_without_a_trailing_separator() {
+        let slug = slug(&format!("{} b", "a".repeat(MAX_SLUG_LEN - 1)));
+        assert_eq!(slug, "a".repeat(MAX_SLUG_LEN - 1));
+    }
+
+    #[test]
+    fn database_names_are_valid() {
+        for company in ["Acme Corp", "3M", "\"; DROP TABLE users; --", &"Long Company Name ".repeat(10)] {
+            let name = database_name(company);
+            assert!(is_valid_database_name(&name), "{}", name);
+        }
+        assert!(database_name("Acme Corp").starts_with("acme_corp_"));
+    }
+
+    #[test]
+    fn invalid_database_names_are_refused() {
+        for name in ["", "Acme", "1acme", "acme-corp", "acme corp", &"a".repeat(MAX_DATABASE_NAME_LEN + 1)] {
+            assert!(!is_valid_database_name(name), "{}", name);
+        }
+    }
+}
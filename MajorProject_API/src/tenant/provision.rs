@@ -0,0 +1,226 @@
+use std::fmt;
+use actix_web::web;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use tracing::{error, info, warn};
+use crate::connect_sql::no_sql;
+use crate::connect_sql::sql_handler::{evict_user_db_pool, DbPool};
+use crate::models::user_requests::User;
+use crate::schema::users;
+use crate::tenant::naming::{is_valid_database_name, quote_ident};
+use crate::tenant::tenancy::{self, Tenancy};
+
+diesel::table! {
+    pg_catalog.pg_database (datname) {
+        datname -> Text,
+    }
+}
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("Employee");
+
+#[derive(Debug)]
+pub enum ProvisionError {
+    InvalidName(String),
+    AdminInsert(String),
+    CreateDatabase(String),
+    Migrations(String),
+    Mongo(String),
+    Internal(String),
+}
+
+impl fmt::Display for ProvisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProvisionError::InvalidName(name) => write!(f, "invalid tenant database name `{}`", name),
+            ProvisionError::AdminInsert(e) => write!(f, "failed to insert admin user: {}", e),
+            ProvisionError::CreateDatabase(e) => write!(f, "failed to create tenant database: {}", e),
+            ProvisionError::Migrations(e) => write!(f, "failed to migrate tenant database: {}", e),
+            ProvisionError::Mongo(e) => write!(f, "failed to initialise tenant inventory: {}", e),
+            ProvisionError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// What has been created so far, undone in reverse order if a later step fails.
+#[derive(Default)]
+struct Created {
+    user_id: Option<i32>,
+    database: bool,
+    mongo: bool,
+}
+
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(f).await.map_err(|e| e.to_string())?
+}
+
+/// Run the pending `Employee` migrations against a tenant database,
+/// returning the versions that were applied.
+pub fn run_migrations(database: &str) -> Result<Vec<String>, ProvisionError> {
+    let tenancy = tenancy::lookup(database).map_err(|e| ProvisionError::Migrations(format!("{:?}", e)))?;
+    run_migrations_as(database, tenancy)
+}
+
+/// Same, for a tenant whose tables are (or are about to be) stored as `tenancy`.
+/// In schema mode diesel's bookkeeping table lives in the tenant's schema too.
+pub fn run_migrations_as(database: &str, tenancy: Tenancy) -> Result<Vec<String>, ProvisionError> {
+    let mut conn = tenancy::connect(database, tenancy).map_err(ProvisionError::Migrations)?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .map(|versions| versions.iter().map(ToString::to_string).collect())
+        .map_err(|e| ProvisionError::Migrations(e.to_string()))
+}
+
+/// Create a company: the admin row, its Postgres database with the `Employee`
+/// schema, and its Mongo inventory database.
+///
+/// Either all of it exists afterwards or, as far as compensation succeeds, none
+/// of it does. Returns the new admin's `user_id`.
+pub async fn provision(pool: &DbPool, new_user: User) -> Result<i32, ProvisionError> {
+    let database = new_user.database_name.clone();
+    if !is_valid_database_name(&database) {
+        return Err(ProvisionError::InvalidName(database));
+    }
+    let tenancy: Tenancy = new_user.tenancy.parse().map_err(ProvisionError::Internal)?;
+
+    let mut created = Created::default();
+    let result = run_steps(pool, new_user, tenancy, &mut created).await;
+    if let Err(err) = &result {
+        warn!("Provisioning `{}` failed ({}); rolling back", database, err);
+        compensate(pool, &database, tenancy, created).await;
+    }
+    result
+}
+
+async fn run_steps(pool: &DbPool, new_user: User, tenancy: Tenancy, created: &mut Created) -> Result<i32, ProvisionError> {
+    let database = new_user.database_name.clone();
+
+    let insert_pool = pool.clone();
+    let user_id = blocking(move || {
+        let mut conn = insert_pool.get().map_err(|e| e.to_string())?;
+        diesel::insert_into(users::table)
+            .values(&new_user)
+            .returning(users::user_id)
+            .get_result::<i32>(&mut conn)
+            .map_err(|e| e.to_string())
+    }).await.map_err(ProvisionError::AdminInsert)?;
+    created.user_id = Some(user_id);
+
+    create_storage(pool, &database, tenancy).await.map_err(ProvisionError::CreateDatabase)?;
+    created.database = true;
+
+    let migrate_database = database.clone();
+    web::block(move || run_migrations_as(&migrate_database, tenancy))
+        .await
+        .map_err(|e| ProvisionError::Internal(e.to_string()))??;
+
+    created.mongo = true; // a failed insert may still have created the database
+    no_sql::get_database(&database)
+        .await
+        .map_err(|e| ProvisionError::Mongo(e.to_string()))?;
+
+    info!("Provisioned tenant `{}` ({}) for user {}", database, tenancy, user_id);
+    Ok(user_id)
+}
+
+fn database_exists(conn: &mut PgConnection, name: &str) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        pg_database::table.filter(pg_database::datname.eq(name)),
+    )).get_result(conn)
+}
+
+// The shared database is created by the first schema-mode tenant
+fn ensure_shared_database(conn: &mut PgConnection) -> Result<(), String> {
+    let shared = tenancy::shared_database();
+    if database_exists(conn, &shared).map_err(|e| e.to_string())? {
+        return Ok(());
+    }
+    match sql_query(format!("CREATE DATABASE {}", quote_ident(&shared))).execute(conn) {
+        Ok(_) => Ok(()),
+        // Lost a race with another provisioning request
+        Err(_) if database_exists(conn, &shared).unwrap_or(false) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Create the empty database or schema a tenant's tables will live in.
+pub async fn create_storage(pool: &DbPool, database: &str, tenancy: Tenancy) -> Result<(), String> {
+    let create_pool = pool.clone();
+    let database = database.to_string();
+    // CREATE DATABASE can't run inside a transaction, hence the compensation
+    blocking(move || {
+        let mut conn = create_pool.get().map_err(|e| e.to_string())?;
+        match tenancy {
+            Tenancy::Database => sql_query(format!("CREATE DATABASE {}", quote_ident(&database)))
+                .execute(&mut conn)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Tenancy::Schema => {
+                ensure_shared_database(&mut conn)?;
+                let target = tenancy::target(&database, Tenancy::Schema).map_err(|e| format!("{:?}", e))?;
+                let mut shared = PgConnection::establish(&target.url).map_err(|e| e.to_string())?;
+                sql_query(format!("CREATE SCHEMA {}", quote_ident(&database)))
+                    .execute(&mut shared)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }).await
+}
+
+/// Drop a tenant's database (`WITH (FORCE)`, disconnecting anyone still
+/// connected) or its schema in the shared database.
+pub async fn drop_storage(pool: &DbPool, database: &str, tenancy: Tenancy) -> Result<(), String> {
+    evict_user_db_pool(database);
+    let drop_pool = pool.clone();
+    let database = database.to_string();
+    blocking(move || match tenancy {
+        Tenancy::Database => {
+            let mut conn = drop_pool.get().map_err(|e| e.to_string())?;
+            sql_query(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", quote_ident(&database)))
+                .execute(&mut conn)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        Tenancy::Schema => {
+            let target = tenancy::target(&database, Tenancy::Schema).map_err(|e| format!("{:?}", e))?;
+            let mut shared = PgConnection::establish(&target.url).map_err(|e| e.to_string())?;
+            sql_query(format!("DROP SCHEMA IF EXISTS {} CASCADE", quote_ident(&database)))
+                .execute(&mut shared)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+    }).await
+}
+
+// Best effort: whatever can't be undone is logged for manual cleanup
+async fn compensate(pool: &DbPool, database: &str, tenancy: Tenancy, created: Created) {
+    if created.mongo {
+        if let Err(e) = no_sql::drop_database(database).await {
+            error!("Manual cleanup needed: failed to drop Mongo database `{}`: {}", database, e);
+        }
+    }
+
+    if created.database {
+        if let Err(e) = drop_storage(pool, database, tenancy).await {
+            error!("Manual cleanup needed: failed to drop database `{}`: {}", database, e);
+        }
+    }
+
+    if let Some(id) = created.user_id {
+        let delete_pool = pool.clone();
+        let deleted = blocking(move || {
+            let mut conn = delete_pool.get().map_err(|e| e.to_string())?;
+            diesel::delete(users::table.filter(users::user_id.eq(id)))
+                .execute(&mut conn)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }).await;
+        if let Err(e) = deleted {
+            error!("Manual cleanup needed: failed to delete admin user {}: {}", id, e);
+        }
+    }
+}
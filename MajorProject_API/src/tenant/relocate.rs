@@ -0,0 +1,115 @@
+use std::io::{Read, Write};
+use diesel::pg::CopyFormat;
+use diesel::prelude::*;
+use diesel::sql_query;
+use tracing::{error, info};
+use crate::connect_sql::sql_handler::{evict_user_db_pool, DbPool};
+use crate::employee_schema::{api_keys, employees, logs, order_lines, order_status_history, orders, password_reset_tokens, sale_lines, sales};
+use crate::schema::users;
+use crate::tenant::provision::{blocking, create_storage, drop_storage, run_migrations_as};
+use crate::tenant::tenancy::{self, Tenancy};
+
+// Copy one table with COPY ... (FORMAT binary); both sides are on the same migration version
+macro_rules! copy_table {
+    ($source:expr, $target:expr, $table:path) => {{
+        let mut data = Vec::new();
+        diesel::copy_to($table)
+            .with_format(CopyFormat::Binary)
+            .load_raw($source)?
+            .read_to_end(&mut data)
+            .map_err(io_error)?;
+        diesel::copy_from($table)
+            .from_raw_data($table, |out: &mut dyn Write| out.write_all(&data).map_err(io_error))
+            .with_format(CopyFormat::Binary)
+            .execute($target)?;
+    }};
+}
+
+/// Serial columns whose sequences have to catch up with the copied rows.
+const SERIAL_COLUMNS: &[(&str, &str)] = &[
+    ("employees", "employee_id"),
+    ("orders", "order_id"),
+    ("order_lines", "line_id"),
+    ("order_status_history", "history_id"),
+    ("sales", "sale_id"),
+    ("sale_lines", "line_id"),
+    ("logs", "log_id"),
+    ("password_reset_tokens", "token_id"),
+    ("api_keys", "key_id"),
+];
+
+fn io_error(e: std::io::Error) -> diesel::result::Error {
+    diesel::result::Error::SerializationError(Box::new(e))
+}
+
+fn copy_tables(source: &mut PgConnection, target: &mut PgConnection) -> QueryResult<()> {
+    target.transaction(|target| {
+        copy_table!(source, target, employees::table);
+        copy_table!(source, target, orders::table);
+        copy_table!(source, target, order_lines::table);
+        copy_table!(source, target, order_status_history::table);
+        copy_table!(source, target, sales::table);
+        copy_table!(source, target, sale_lines::table);
+        copy_table!(source, target, logs::table);
+        copy_table!(source, target, password_reset_tokens::table);
+        copy_table!(source, target, api_keys::table);
+
+        for (table, column) in SERIAL_COLUMNS {
+            sql_query(format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', '{column}'), COALESCE((SELECT MAX({column}) FROM {table}), 0) + 1, false)"
+            ))
+            .execute(target)?;
+        }
+        Ok(())
+    })
+}
+
+/// Move a tenant between database-per-tenant and schema-per-tenant storage.
+///
+/// The tenant should be quiet while this runs: rows written to the old storage
+/// after they have been copied are lost. The old storage is kept unless
+/// `drop_source` is set, so a failed or regretted move can be undone by hand.
+pub async fn move_tenant(pool: &DbPool, tenant: &str, to: Tenancy, drop_source: bool) -> Result<(), String> {
+    let from = tenancy::lookup(tenant).map_err(|e| format!("{:?}", e))?;
+    if from == to {
+        return Err(format!("`{}` already uses {} tenancy", tenant, to));
+    }
+
+    // Bring the source up to date so both sides have the same tables
+    let migrate_tenant = tenant.to_string();
+    blocking(move || run_migrations_as(&migrate_tenant, from).map_err(|e| e.to_string())).await?;
+
+    create_storage(pool, tenant, to).await?;
+    let copy_tenant = tenant.to_string();
+    let copied = blocking(move || {
+        run_migrations_as(&copy_tenant, to).map_err(|e| e.to_string())?;
+        let mut source = tenancy::connect(&copy_tenant, from)?;
+        let mut target = tenancy::connect(&copy_tenant, to)?;
+        copy_tables(&mut source, &mut target).map_err(|e| e.to_string())
+    }).await;
+    if let Err(e) = copied {
+        if let Err(cleanup) = drop_storage(pool, tenant, to).await {
+            error!("Manual cleanup needed: failed to drop {} `{}`: {}", to, tenant, cleanup);
+        }
+        return Err(e);
+    }
+
+    let switch_pool = pool.clone();
+    let switch_tenant = tenant.to_string();
+    blocking(move || {
+        let mut conn = switch_pool.get().map_err(|e| e.to_string())?;
+        diesel::update(users::table.filter(users::database_name.eq(&switch_tenant)))
+            .set(users::tenancy.eq(to.as_str()))
+            .execute(&mut conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }).await?;
+    // The next request opens a pool against the new storage
+    evict_user_db_pool(tenant);
+    info!("Moved tenant `{}` from {} to {}", tenant, from, to);
+
+    if drop_source {
+        drop_storage(pool, tenant, from).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::str::FromStr;
+use diesel::prelude::*;
+use diesel::r2d2::{self, CustomizeConnection};
+use diesel::sql_query;
+use serde::Deserialize;
+use crate::config::settings;
+use crate::connect_sql::sql_handler::{establish_connection, DbError};
+use crate::schema::users;
+use crate::tenant::naming::quote_ident;
+
+/// Where a tenant's tables live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tenancy {
+    /// Its own database, named after the tenant.
+    Database,
+    /// A schema named after the tenant inside the shared tenant database.
+    Schema,
+}
+
+impl Tenancy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Tenancy::Database => "database",
+            Tenancy::Schema => "schema",
+        }
+    }
+}
+
+impl fmt::Display for Tenancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Tenancy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "database" => Ok(Tenancy::Database),
+            "schema" => Ok(Tenancy::Schema),
+            other => Err(format!("unknown tenancy `{}`; expected `database` or `schema`", other)),
+        }
+    }
+}
+
+/// `tenancy.shared_database`, the database holding schema-mode tenants (default `tenants_shared`).
+pub fn shared_database() -> String {
+    settings::get().tenancy.shared_database.clone()
+}
+
+/// How to reach a tenant's tables.
+#[derive(Debug, Clone)]
+pub struct TenantTarget {
+    pub url: String,
+    /// `search_path` to set on every connection in schema mode.
+    pub schema: Option<String>,
+}
+
+pub fn target(tenant: &str, tenancy: Tenancy) -> Result<TenantTarget, DbError> {
+    let base_url = &settings::get().database.admin_url;
+    Ok(match tenancy {
+        Tenancy::Database => TenantTarget { url: format!("{}{}", base_url, tenant), schema: None },
+        Tenancy::Schema => TenantTarget { url: format!("{}{}", base_url, shared_database()), schema: Some(tenant.to_string()) },
+    })
+}
+
+/// The tenancy recorded for `tenant`. Tenants unknown to the admin database
+/// are assumed to be database-per-tenant, as every tenant used to be.
+pub fn lookup(tenant: &str) -> Result<Tenancy, DbError> {
+    let pool = establish_connection().map_err(DbError::ConnectionPoolError)?;
+    let mut conn = pool.get().map_err(|e| DbError::ConnectionPoolError(e.to_string()))?;
+    let recorded = users::table
+        .filter(users::database_name.eq(tenant))
+        .select(users::tenancy)
+        .first::<String>(&mut conn)
+        .optional()
+        .map_err(|e| DbError::ConnectionPoolError(e.to_string()))?;
+    match recorded {
+        Some(tenancy) => tenancy.parse().map_err(DbError::ConnectionPoolError),
+        None => Ok(Tenancy::Database),
+    }
+}
+
+fn set_search_path(conn: &mut PgConnection, schema: &str) -> QueryResult<()> {
+    sql_query(format!("SET search_path TO {}", quote_ident(schema))).execute(conn).map(|_| ())
+}
+
+/// Points every new pooled connection at one tenant's schema.
+#[derive(Debug)]
+pub struct SearchPath(pub String);
+
+impl CustomizeConnection<PgConnection, r2d2::Error> for SearchPath {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+        set_search_path(conn, &self.0).map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// A single connection to a tenant's tables, for migrations and maintenance.
+pub fn connect(tenant: &str, tenancy: Tenancy) -> Result<PgConnection, String> {
+    let target = target(tenant, tenancy).map_err(|e| format!("{:?}", e))?;
+    let mut conn = PgConnection::establish(&target.url).map_err(|e| e.to_string())?;
+    if let Some(schema) = &target.schema {
+        set_search_path(&mut conn, schema).map_err(|e| e.to_string())?;
+    }
+    Ok(conn)
+}
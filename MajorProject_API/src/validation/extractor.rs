@@ -0,0 +1,39 @@
+use std::ops::Deref;
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+use crate::error::app_error::AppError;
+
+/// JSON body that has passed its `#[validate(...)]` rules.
+///
+/// Use in place of `web::Json<T>`: invalid bodies are answered with 422 and the
+/// failing fields before the handler runs.
+#[derive(Debug)]
+pub struct Validated<T>(pub T);
+
+impl<T> Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for Validated<T>
+where
+    T: DeserializeOwned + Validate + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let body = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let body = body.await?.into_inner();
+            body.validate().map_err(AppError::from)?;
+            Ok(Validated(body))
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use validator::ValidationError;
+use crate::auth::{password, permission};
+use crate::models::tools::{OrderLineRequest, Status};
+
+fn invalid(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
+
+/// A struct-level error reported under one of the struct's fields.
+fn on_field(mut err: ValidationError, field: &'static str) -> ValidationError {
+    err.add_param(Cow::Borrowed("field"), &field);
+    err
+}
+
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    match value.trim().is_empty() {
+        true => Err(invalid("blank", "must not be blank")),
+        false => Ok(()),
+    }
+}
+
+/// The password policy of [`password::check_policy`].
+pub fn password_policy(value: &str) -> Result<(), ValidationError> {
+    password::check_policy(value).map_err(|message| invalid("password_policy", message))
+}
+
+/// A comma-separated list of permissions and role names.
+pub fn permission_list(value: &str) -> Result<(), ValidationError> {
+    permission::parse_list(value).map(|_| ()).map_err(|message| invalid("unknown_permission", message))
+}
+
+/// Each entry names a known permission.
+pub fn permission_names(values: &[String]) -> Result<(), ValidationError> {
+    for value in values {
+        value.parse::<permission::Permission>().map_err(|message| invalid("unknown_permission", message))?;
+    }
+    Ok(())
+}
+
+pub fn order_status(value: &str) -> Result<(), ValidationError> {
+    value.parse::<Status>().map(|_| ()).map_err(|message| invalid("unknown_status", message))
+}
+
+/// A product appears on one line only, so its quantities are not split across lines.
+pub fn distinct_order_products(lines: &[OrderLineRequest]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+    match lines.iter().all(|line| seen.insert(line.product_name.trim())) {
+        true => Ok(()),
+        false => Err(invalid("duplicate", "each product may only appear on one line")),
+    }
+}
+
+/// Received quantities go with a partial delivery and nothing else.
+pub fn received_lines(partial_delivery: bool, has_received: bool) -> Result<(), ValidationError> {
+    match (partial_delivery, has_received) {
+        (true, false) => Err(on_field(invalid("required", "list what arrived for a partial delivery"), "received")),
+        (false, true) => Err(on_field(invalid("unexpected", "only allowed with `partially_delivered`"), "received")),
+        _ => Ok(()),
+    }
+}
+
+/// A discount is an amount taken off a line, at most the line's full price.
+pub fn discount(discount: f64, quantity: i32, unit_price: f64) -> Result<(), ValidationError> {
+    if !discount.is_finite() || discount < 0.0 {
+        return Err(on_field(invalid("range", "must be zero or more"), "discount"));
+    }
+    match discount <= f64::from(quantity) * unit_price {
+        true => Ok(()),
+        false => Err(on_field(invalid("range", "must not exceed quantity times unit price"), "discount")),
+    }
+}
+
+/// A finite, non-negative amount.
+pub fn price(value: f64) -> Result<(), ValidationError> {
+    match value.is_finite() && value >= 0.0 {
+        true => Ok(()),
+        false => Err(invalid("range", "must be zero or more")),
+    }
+}
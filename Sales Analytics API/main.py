@@ -56,21 +56,11 @@ async def analytics_data_from_sql(db_name: str):
     await database.connect()
     
     query = """
-        WITH exploded_sales AS (
-            SELECT 
-                sale_id,
-                UNNEST(product_id) AS product,
-                UNNEST(quantity_sold) AS quantity,
-                UNNEST(price) AS unit_price,
-                total_price,
-                sale_date
-            FROM sales
-        ),
-        revenue_cte AS (
+        WITH revenue_cte AS (
             SELECT 
                 SUM(quantity * unit_price * 1.05) AS total_revenue,
                 SUM(quantity) AS items_sold
-            FROM exploded_sales
+            FROM sale_lines
         ),
         order_count_cte AS (
             SELECT COUNT(*) AS total_orders FROM sales
@@ -108,16 +98,14 @@ async def product_summary(db_name: str):
 
         query ="""
                 SELECT
-                flat.product,
-                flat.category,
-                SUM(flat.quantity) AS units_sold,
-                SUM(flat.quantity * flat.price) AS revenue
+                product_name AS product,
+                category,
+                SUM(quantity) AS units_sold,
+                SUM(line_total) AS revenue
                 FROM
-                sales,
-                LATERAL unnest(product_id, quantity_sold, price, categories)
-                    AS flat(product, quantity, price, category)
+                sale_lines
                 GROUP BY
-                flat.product, flat.category
+                product_name, category
                 ORDER BY
                 revenue DESC
                 """
@@ -158,11 +146,11 @@ async def last30day_sales_graph(db_name:str):
     query = """
     SELECT
         TO_CHAR(DATE(sale_date), 'Mon DD') AS day_label,
-        SUM(qty) AS sales,
-        SUM(total_price) AS profit
+        SUM(sale_lines.quantity) AS sales,
+        SUM(sale_lines.line_total) AS profit
     FROM
-        sales,
-        unnest(quantity_sold) AS qty
+        sales
+        JOIN sale_lines ON sale_lines.sale_id = sales.sale_id
     WHERE
         sale_date >= CURRENT_DATE - INTERVAL '30 days'
     GROUP BY
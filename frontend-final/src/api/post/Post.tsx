@@ -44,12 +44,17 @@ export const AdminLogin = async (
 
 };
 
+interface OrderLineData {
+    sku?: string
+    product_name: string
+    category: string
+    quantity: number
+    unit_cost: number
+}
+
 interface OrderData {
     supplier_name: string
-    products: { [key: string]: number }
-    categories : string[]
-    price : number[]
-    
+    lines: OrderLineData[]
 }
 
 export const createOrder = async (orderData: OrderData): Promise<any> => {
@@ -98,11 +103,18 @@ export const addEmployeeAPI = async (email :string , password : string, name : s
     }
 }
 
-export const makeSales = async (sale_by : number , products : Record<string, number>, price : number[],categories : string[]) =>{
-    console.log(JSON.stringify({ sale_by, products, price }, null, 2));
+export interface SaleLineData {
+    product_name: string
+    category: string
+    quantity: number
+    unit_price: number
+    discount?: number
+}
+
+export const makeSales = async (lines : SaleLineData[]) =>{
     try{
         const response = await api.post('sale_set',
-            JSON.stringify({ sale_by, products, categories ,price }, null, 2)
+            { lines }
         ,{withCredentials:true});
         console.log("respond : ",response.data)
     }catch(error:unknown){
@@ -105,6 +105,7 @@ export default function POSPage() {
             date: new Date().toISOString(),
             items: cart.map((cartItem) => ({
                 name: cartItem.item.name,
+                category: cartItem.item.category,
                 quantity: cartItem.quantity,
                 price: cartItem.item.price,
                 total: cartItem.item.price * cartItem.quantity,
@@ -1,7 +1,7 @@
 "use client"
 
 import { Receipt, Printer, ArrowRight } from "lucide-react"
-import { makeSales } from "../../api/post/Post"
+import { makeSales, SaleLineData } from "../../api/post/Post"
 import { useEffect } from "react"
 import { useAuth } from "../../contexts/auth-context"
 
@@ -16,26 +16,17 @@ export default function SaleCompleteModal({ isOpen, onClose, receiptData }: Sale
     useEffect(() => {
         const sendSale = async () => {
             if (isOpen && receiptData && user != null) {
-                const sale_by = Number(user.id) || 1;
-
-                const products: Record<string, number> = {};
-                const price: number[] = [];
-
-                receiptData.items.forEach((item: any) => {
-                    const name = item.category;
-                    const quantity = Number(item.quantity) || 0;
-                    const itemPrice = parseFloat(item.total?.toString() || "0");
-
-                    if (name && quantity > 0) {
-                        products[name] = quantity;
-                    }
-
-                    if (!isNaN(itemPrice)) {
-                        price.push(itemPrice);
-                    }
-                });
-
-                await makeSales(sale_by, products, price);
+                // Totals are computed by the server from quantity and unit price
+                const lines: SaleLineData[] = receiptData.items
+                    .map((item: any) => ({
+                        product_name: item.name,
+                        category: item.category,
+                        quantity: Number(item.quantity) || 0,
+                        unit_price: parseFloat(item.price?.toString() || "0"),
+                    }))
+                    .filter((line: SaleLineData) => line.product_name && line.quantity > 0 && !isNaN(line.unit_price));
+
+                await makeSales(lines);
             }
         };
 
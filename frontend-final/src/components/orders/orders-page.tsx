@@ -19,16 +19,48 @@ import { getInventoryOrder } from "../../api/get/get"
 import { createOrder } from "../../api/post/Post"
 import { statusChange } from "../../api/patch/patch"
 
+interface OrderLine {
+  line_id: number
+  order_id: number
+  sku: string | null
+  product_name: string
+  category: string
+  quantity: number
+  unit_cost: number
+}
+
+interface OrderStatusChange {
+  history_id: number
+  from_status: string | null
+  to_status: string
+  changed_by: number | null
+  actor_kind: string | null
+  changed_at: string
+}
+
 interface Order {
   order_id: number
   supplier_name: string
-  product_id: string[]
-  quantity_ordered: number[]
-  price : number[]
+  lines: OrderLine[]
+  history: OrderStatusChange[]
   order_date: string
   status: string
 }
 
+// Statuses the API lets each status move to. Partial deliveries need received
+// quantities, so this page only offers full deliveries.
+const NEXT_STATUSES: { [status: string]: string[] } = {
+  pending: ["approved", "cancelled"],
+  approved: ["shipped", "cancelled"],
+  shipped: ["delivered", "cancelled"],
+  partially_delivered: ["delivered"],
+}
+
+const statusLabel = (status: string) => {
+  const words = status.replace("_", " ")
+  return words.charAt(0).toUpperCase() + words.slice(1)
+}
+
 interface OrdersResponse {
   orders: Order[]
 }
@@ -39,7 +71,9 @@ interface EditingOrder extends Order {
 
 interface NewOrderProduct {
   name: string
+  category: string
   quantity: number
+  unit_cost: number
 }
 
 export default function OrdersPage() {
@@ -52,9 +86,7 @@ export default function OrdersPage() {
   const [isModalOpen, setIsModalOpen] = useState(false)
   const [isNewOrderModalOpen, setIsNewOrderModalOpen] = useState(false)
   const [newOrderSupplier, setNewOrderSupplier] = useState("")
-  const [priceArray , setpriceArray] = useState<number[]>([])
-  const [categories,setCategories] = useState<string[]>([])
-  const [newOrderProducts, setNewOrderProducts] = useState<NewOrderProduct[]>([{ name: "", quantity: 1 }])
+  const [newOrderProducts, setNewOrderProducts] = useState<NewOrderProduct[]>([{ name: "", category: "", quantity: 1, unit_cost: 0 }])
   const [isSubmitting, setIsSubmitting] = useState(false)
   const [submitError, setSubmitError] = useState("")
 
@@ -88,20 +120,25 @@ export default function OrdersPage() {
     }))
   }
 
-  const handleSave = (orderId: number) => {
-
+  const handleSave = async (orderId: number) => {
     const editedOrder = editingOrders[orderId]
-    if (editedOrder) {
-      setOrders((prev) =>
-        prev.map((order) => (order.order_id === orderId ? { ...editedOrder, isEditing: false } : order)),
-      )
-      setEditingOrders((prev) => {
-        const newState = { ...prev }
-        delete newState[orderId]
-        return newState
-      })
+    if (!editedOrder) return
+    setEditingOrders((prev) => {
+      const newState = { ...prev }
+      delete newState[orderId]
+      return newState
+    })
+    const current = orders.find((order) => order.order_id === orderId)
+    if (current && current.status === editedOrder.status) return
+
+    try {
+      await statusChange(orderId, editedOrder.status)
+      const updatedOrders = await getInventoryOrder()
+      setOrders(updatedOrders.orders)
+    } catch (err) {
+      const typedError = err as Error
+      console.error(typedError.message)
     }
-    statusChange(orderId, editedOrder.status);
   }
 
   const handleCancel = (orderId: number) => {
@@ -117,7 +154,7 @@ export default function OrdersPage() {
       ...prev,
       [orderId]: {
         ...prev[orderId],
-        product_id: prev[orderId].product_id.map((p, i) => (i === index ? value : p)),
+        lines: prev[orderId].lines.map((line, i) => (i === index ? { ...line, product_name: value } : line)),
       },
     }))
   }
@@ -127,7 +164,7 @@ export default function OrdersPage() {
       ...prev,
       [orderId]: {
         ...prev[orderId],
-        quantity_ordered: prev[orderId].quantity_ordered.map((q, i) => (i === index ? value : q)),
+        lines: prev[orderId].lines.map((line, i) => (i === index ? { ...line, quantity: value } : line)),
       },
     }))
   }
@@ -145,12 +182,12 @@ export default function OrdersPage() {
   const handleNewOrderOpen = () => {
     setIsNewOrderModalOpen(true)
     setNewOrderSupplier("")
-    setNewOrderProducts([{ name: "", quantity: 1 }])
+    setNewOrderProducts([{ name: "", category: "", quantity: 1, unit_cost: 0 }])
     setSubmitError("")
   }
 
   const handleAddProduct = () => {
-    setNewOrderProducts([...newOrderProducts, { name: "", quantity: 1 }])
+    setNewOrderProducts([...newOrderProducts, { name: "", category: "", quantity: 1, unit_cost: 0 }])
   }
 
   const handleRemoveProduct = (index: number) => {
@@ -159,7 +196,7 @@ export default function OrdersPage() {
     }
   }
 
-  const handleNewProductChange = (index: number, field: "name" | "quantity", value: string | number) => {
+  const handleNewProductChange = (index: number, field: keyof NewOrderProduct, value: string | number) => {
     const updatedProducts = [...newOrderProducts]
     updatedProducts[index] = {
       ...updatedProducts[index],
@@ -175,9 +212,11 @@ export default function OrdersPage() {
       return
     }
 
-    const invalidProducts = newOrderProducts.some((p) => !p.name.trim() || p.quantity <= 0)
+    const invalidProducts = newOrderProducts.some(
+      (p) => !p.name.trim() || !p.category.trim() || p.quantity <= 0 || p.unit_cost < 0,
+    )
     if (invalidProducts) {
-      setSubmitError("Please enter valid product names and quantities")
+      setSubmitError("Please enter valid product names, categories, quantities and costs")
       return
     }
 
@@ -186,16 +225,14 @@ export default function OrdersPage() {
 
     try {
       // Format data for API
-      const productsObject: { [key: string]: number } = {}
-      newOrderProducts.forEach((product) => {
-        productsObject[product.name] = product.quantity
-      })
-
       const orderData = {
         supplier_name: newOrderSupplier,
-        products: productsObject,
-        categories : categories,
-        price : priceArray
+        lines: newOrderProducts.map((product) => ({
+          product_name: product.name,
+          category: product.category,
+          quantity: product.quantity,
+          unit_cost: product.unit_cost,
+        })),
       }
 
       // Call API
@@ -228,7 +265,7 @@ export default function OrdersPage() {
 
   // Calculate summary statistics
   const totalOrders = orders.length
-  const totalItems = orders.reduce((sum, order) => sum + order.quantity_ordered.reduce((a, b) => a + b, 0), 0)
+  const totalItems = orders.reduce((sum, order) => sum + order.lines.reduce((a, line) => a + line.quantity, 0), 0)
   const pendingOrders = orders.filter((order) => order.status === "pending").length
   const deliveredOrders = orders.filter((order) => order.status === "delivered").length
 
@@ -332,6 +369,9 @@ export default function OrdersPage() {
             >
               <option value="">All Statuses</option>
               <option value="pending">Pending</option>
+              <option value="approved">Approved</option>
+              <option value="shipped">Shipped</option>
+              <option value="partially_delivered">Partially delivered</option>
               <option value="delivered">Delivered</option>
               <option value="cancelled">Cancelled</option>
             </select>
@@ -422,9 +462,11 @@ export default function OrdersPage() {
                               onChange={(e) => handleStatusChange(order.order_id, e.target.value)}
                               className="block w-full pl-3 pr-10 py-1 text-sm border border-gray-300 rounded-md focus:outline-none focus:ring-1 focus:ring-indigo-500 focus:border-indigo-500"
                             >
-                              <option value="pending">Pending</option>
-                              <option value="delivered">Delivered</option>
-                              <option value="cancelled">Cancelled</option>
+                              {[order.status, ...(NEXT_STATUSES[order.status] || [])].map((status) => (
+                                <option key={status} value={status}>
+                                  {statusLabel(status)}
+                                </option>
+                              ))}
                             </select>
                           ) : (
                             <span
@@ -433,12 +475,12 @@ export default function OrdersPage() {
                                   ? "bg-green-100 text-green-800"
                                   : order.status === "pending"
                                     ? "bg-yellow-100 text-yellow-800"
-                                    : order.status === "processing"
+                                    : order.status === "approved" || order.status === "shipped" || order.status === "partially_delivered"
                                       ? "bg-blue-100 text-blue-800"
                                       : "bg-red-100 text-red-800"
                                 }`}
                             >
-                              {order.status.charAt(0).toUpperCase() + order.status.slice(1)}
+                              {statusLabel(order.status)}
                             </span>
                           )}
                         </td>
@@ -466,7 +508,7 @@ export default function OrdersPage() {
                               >
                                 View
                               </button>
-                              {order.status === "pending" && (
+                              {NEXT_STATUSES[order.status] && (
                                 <button onClick={() => handleEdit(order)} className="text-gray-600 hover:text-gray-900">
                                   Edit
                                 </button>
@@ -483,18 +525,18 @@ export default function OrdersPage() {
                                 <h4 className="text-sm font-medium text-gray-900 mb-2">Products and Quantities</h4>
                                 {isEditing ? (
                                   <div className="space-y-2">
-                                    {isEditing.product_id.map((product, index) => (
-                                      <div key={index} className="flex flex-col sm:flex-row gap-2 sm:gap-4">
+                                    {isEditing.lines.map((line, index) => (
+                                      <div key={line.line_id} className="flex flex-col sm:flex-row gap-2 sm:gap-4">
                                         <input
                                           type="text"
-                                          value={product}
+                                          value={line.product_name}
                                           onChange={(e) => handleProductChange(order.order_id, index, e.target.value)}
                                           className="flex-1 px-3 py-1 text-sm border border-gray-300 rounded-md focus:outline-none focus:ring-1 focus:ring-indigo-500 focus:border-indigo-500"
                                           placeholder="Product name"
                                         />
                                         <input
                                           type="number"
-                                          value={isEditing.quantity_ordered[index]}
+                                          value={line.quantity}
                                           onChange={(e) =>
                                             handleQuantityChange(order.order_id, index, Number.parseInt(e.target.value))
                                           }
@@ -507,12 +549,12 @@ export default function OrdersPage() {
                                   </div>
                                 ) : (
                                   <div className="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 gap-4">
-                                    {order.product_id.map((product, index) => (
+                                    {order.lines.map((line) => (
                                       <div
-                                        key={index}
+                                        key={line.line_id}
                                         className="text-sm text-gray-600 bg-white p-3 rounded-md shadow-sm"
                                       >
-                                        <span className="font-medium">{product}:</span> {order.quantity_ordered[index]}{" "}
+                                        <span className="font-medium">{line.product_name}:</span> {line.quantity}{" "}
                                         units
                                       </div>
                                     ))}
@@ -629,12 +671,12 @@ export default function OrdersPage() {
                         ? "bg-green-100 text-green-800"
                         : selectedOrder.status === "pending"
                           ? "bg-yellow-100 text-yellow-800"
-                          : selectedOrder.status === "processing"
+                          : selectedOrder.status === "approved" || selectedOrder.status === "shipped" || selectedOrder.status === "partially_delivered"
                             ? "bg-blue-100 text-blue-800"
                             : "bg-red-100 text-red-800"
                       }`}
                   >
-                    {selectedOrder.status.charAt(0).toUpperCase() + selectedOrder.status.slice(1)}
+                    {statusLabel(selectedOrder.status)}
                   </span>
                 </div>
               </div>
@@ -643,11 +685,11 @@ export default function OrdersPage() {
                 <h3 className="text-lg font-medium text-gray-900 mb-4">Order Items</h3>
                 <div className="bg-gray-50 rounded-lg p-4">
                   <div className="grid grid-cols-1 sm:grid-cols-2 gap-3">
-                    {selectedOrder.product_id.map((product, index) => (
-                      <div key={index} className="flex justify-between items-center bg-white p-3 rounded-md shadow-sm">
-                        <div className="text-sm font-medium text-gray-900">{product}</div>
+                    {selectedOrder.lines.map((line) => (
+                      <div key={line.line_id} className="flex justify-between items-center bg-white p-3 rounded-md shadow-sm">
+                        <div className="text-sm font-medium text-gray-900">{line.product_name}</div>
                         <div className="text-sm text-gray-600 font-semibold">
-                          {selectedOrder.quantity_ordered[index]} units
+                          {line.quantity} units
                         </div>
                       </div>
                     ))}
@@ -658,41 +700,25 @@ export default function OrdersPage() {
               <div className="mt-6">
                 <h3 className="text-lg font-medium text-gray-900 mb-4">Order Timeline</h3>
                 <div className="space-y-4">
-                  <div className="flex">
-                    <div className="flex flex-col items-center mr-4">
-                      <div className="w-3 h-3 bg-green-500 rounded-full"></div>
-                      <div className="h-full w-0.5 bg-gray-200"></div>
-                    </div>
-                    <div>
-                      <p className="text-sm font-medium text-gray-900">Order Created</p>
-                      <p className="text-xs text-gray-500">{new Date(selectedOrder.order_date).toLocaleString()}</p>
-                    </div>
-                  </div>
-
-                  {selectedOrder.status !== "pending" && (
-                    <div className="flex">
+                  {selectedOrder.history.map((change, index) => (
+                    <div key={change.history_id} className="flex">
                       <div className="flex flex-col items-center mr-4">
-                        <div className="w-3 h-3 bg-blue-500 rounded-full"></div>
-                        <div className="h-full w-0.5 bg-gray-200"></div>
+                        <div
+                          className={`w-3 h-3 rounded-full ${change.to_status === "cancelled" ? "bg-red-500" : change.to_status === "delivered" || change.from_status === null ? "bg-green-500" : "bg-blue-500"}`}
+                        ></div>
+                        {index < selectedOrder.history.length - 1 && <div className="h-full w-0.5 bg-gray-200"></div>}
                       </div>
                       <div>
-                        <p className="text-sm font-medium text-gray-900">Processing Started</p>
-                        <p className="text-xs text-gray-500">Status updated to processing</p>
+                        <p className="text-sm font-medium text-gray-900">
+                          {change.from_status === null ? "Order Created" : statusLabel(change.to_status)}
+                        </p>
+                        <p className="text-xs text-gray-500">
+                          {new Date(change.changed_at).toLocaleString()}
+                          {change.changed_by !== null && ` by ${change.actor_kind} #${change.changed_by}`}
+                        </p>
                       </div>
                     </div>
-                  )}
-
-                  {selectedOrder.status === "delivered" && (
-                    <div className="flex">
-                      <div className="flex flex-col items-center mr-4">
-                        <div className="w-3 h-3 bg-green-500 rounded-full"></div>
-                      </div>
-                      <div>
-                        <p className="text-sm font-medium text-gray-900">Order Delivered</p>
-                        <p className="text-xs text-gray-500">Status updated to delivered</p>
-                      </div>
-                    </div>
-                  )}
+                  ))}
                 </div>
               </div>
             </div>
@@ -789,6 +815,16 @@ export default function OrdersPage() {
                             required
                           />
                         </div>
+                        <div className="sm:w-32">
+                          <input
+                            type="text"
+                            value={product.category}
+                            onChange={(e) => handleNewProductChange(index, "category", e.target.value)}
+                            className="block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm focus:outline-none focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm"
+                            placeholder="Category"
+                            required
+                          />
+                        </div>
                         <div className="flex items-center">
                           <input
                             type="number"
@@ -801,6 +837,18 @@ export default function OrdersPage() {
                             min="1"
                             required
                           />
+                          <input
+                            type="number"
+                            value={product.unit_cost}
+                            onChange={(e) =>
+                              handleNewProductChange(index, "unit_cost", Number.parseFloat(e.target.value) || 0)
+                            }
+                            className="ml-2 block w-24 px-3 py-2 border border-gray-300 rounded-md shadow-sm focus:outline-none focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm"
+                            placeholder="Unit cost"
+                            min="0"
+                            step="0.01"
+                            required
+                          />
                           {newOrderProducts.length > 1 && (
                             <button
                               type="button"